@@ -0,0 +1,77 @@
+pub use super::frame::Attributes;
+use super::{editor::Position, error::Error};
+/// external crate
+pub use termion::{
+    color::Rgb,
+    event::{Key, MouseButton, MouseEvent},
+};
+
+/// 输入事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// 普通按键
+    Key(Key),
+    /// 按住Shift的方向键/Home/End, 用于选择文本
+    Shift(Key),
+    /// 鼠标事件(点击, 拖动, 滚轮), 坐标从1开始
+    Mouse(MouseEvent),
+    /// 终端窗口大小发生变化(SIGWINCH)
+    Resize,
+    /// 收到挂起信号(SIGTSTP, 例如kill -TSTP)
+    Suspend,
+    /// 一段时间内没有其他事件, 用于执行定期任务(如写入交换文件)
+    Tick,
+}
+
+/// 屏幕大小, height为文本区域的高度(不包括状态栏和消息栏)
+#[derive(Debug, Clone, Copy)]
+pub struct Size {
+    pub width: u16,
+    pub height: u16,
+}
+
+/// 输入源: 编辑器从这里读取事件
+/// 终端使用Processor(标准输入及信号), 测试或嵌入时可以使用ScriptedInput按顺序提供事件
+pub trait InputSource {
+    /// 读取下一个事件(阻塞直到有事件发生), 输入结束时返回错误
+    fn read_event(&self) -> Result<Event, Error>;
+}
+
+/// 输出目标: 编辑器把画面绘制到这里
+/// 终端使用Terminal(输出转义序列到标准输出), 测试或嵌入时可以使用MemoryScreen绘制到内存中的字符网格
+pub trait RenderTarget {
+    /// 屏幕大小
+    fn size(&self) -> Size;
+    /// 重新获取屏幕大小(大小变化后调用)
+    fn update_size(&self) -> Result<(), Error>;
+    /// 移动光标
+    fn cursor_position(&self, position: &Position);
+    /// 清空屏幕
+    fn clear_screen(&self);
+    /// 清空光标所在的行
+    fn clear_current_line(&self);
+    /// 在光标处绘制字符串(不换行)
+    fn draw_text(&self, text: &str);
+    /// 在光标处绘制字符串, 然后移动到下一行行首
+    fn draw_row(&self, text: &str);
+    /// 隐藏光标
+    fn cursor_hide(&self);
+    /// 显示光标
+    fn cursor_show(&self);
+    /// 输出一帧画面
+    fn flush(&self) -> Result<(), Error>;
+    /// 设置背景颜色
+    fn set_bg_color(&self, color: Rgb);
+    /// 重置背景颜色
+    fn reset_bg_color(&self);
+    /// 设置前景颜色
+    fn set_fg_color(&self, color: Rgb);
+    /// 重置前景颜色
+    fn reset_fg_color(&self);
+    /// 设置之后绘制的文本的属性(粗体, 下划线, 反色), 使用Attributes::default()恢复
+    fn set_attributes(&self, attributes: Attributes);
+    /// 挂起(恢复终端并停止进程, 继续运行后返回), 不支持挂起的输出目标什么也不做
+    fn suspend(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use super::{document::Document, editor::Position};
+
+/// 缓冲区(打开的一个文件)及其光标状态
+/// 当前缓冲区的文档, 光标位置和偏移量由Editor直接持有, 切换缓冲区时与这里保存的状态交换
+#[derive(Default)]
+pub struct Buffer {
+    document: Option<Document>, // 为None时表示该缓冲区是当前缓冲区
+    cursor_position: Position,
+    offset: Position,
+}
+
+impl Buffer {
+    /// 新建一个缓冲区(不是当前缓冲区)
+    pub fn new(document: Document) -> Self {
+        Self {
+            document: Some(document),
+            ..Self::default()
+        }
+    }
+
+    /// 不是当前缓冲区时返回保存的文档
+    pub fn document(&self) -> Option<&Document> {
+        self.document.as_ref()
+    }
+    /// 不是当前缓冲区时返回保存的文档(可修改, 用于高亮)
+    pub fn document_mut(&mut self) -> Option<&mut Document> {
+        self.document.as_mut()
+    }
+
+    /// 切换到其他缓冲区时保存当前缓冲区的状态
+    pub fn store(&mut self, document: Document, cursor_position: Position, offset: Position) {
+        self.document = Some(document);
+        self.cursor_position = cursor_position;
+        self.offset = offset;
+    }
+
+    /// 切换到该缓冲区时取出保存的状态
+    pub fn take(&mut self) -> (Document, Position, Position) {
+        (
+            self.document.take().unwrap_or_default(),
+            self.cursor_position,
+            self.offset,
+        )
+    }
+}
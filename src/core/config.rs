@@ -0,0 +1,379 @@
+pub use super::document::LineEnding;
+use super::{
+    constants,
+    keymap::{self, Command, Keymap, Preset},
+};
+use std::{collections::HashMap, env, fs, io, path::PathBuf};
+/// external crate
+use termion::color::Rgb;
+use toml::{Table, Value};
+
+/// 编辑器设置
+/// 从配置文件 `$XDG_CONFIG_HOME/lecto/config.toml` 读取(没有设置XDG_CONFIG_HOME时为 `~/.config/lecto/config.toml`),
+/// 文件不存在时使用默认设置. 例如:
+///
+/// ```toml
+/// tab_width = 4
+/// expand_tabs = false
+/// welcome = true
+/// # 类似Vim的模态编辑(普通, 插入, 可视及命令行模式)
+/// vim = false
+/// # 按键绑定的预设: default, emacs或nano, 顶层的设置需要写在第一个表([...])之前
+/// keymap = "default"
+///
+/// [colors]
+/// status_bg = "#efefef"
+/// status_fg = "#3f3f3f"
+///
+/// [filetype.rust]
+/// expand_tabs = true
+///
+/// [filetype.markdown]
+/// highlighting = false
+///
+/// # 按键绑定: 在预设的基础上修改, 命令为none时解除绑定
+/// [keys]
+/// "Ctrl-k Ctrl-s" = "save"
+/// "Ctrl-q" = "none"
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// 是否使用终端的备用屏幕, 使用时退出后恢复启动前的屏幕内容
+    pub alternate_screen: bool,
+    /// 是否启用鼠标(点击移动光标, 滚轮滚动, 拖动选择), 启用后终端自身的鼠标选择需要按住Shift
+    pub mouse: bool,
+    /// 没有打开文件时是否显示欢迎信息
+    pub welcome: bool,
+    /// 界面颜色
+    pub colors: Colors,
+    /// 按键绑定
+    pub keymap: Keymap,
+    /// 是否启用类似Vim的模态编辑, 普通模式下没有对应Vim命令的按键仍然按照按键绑定执行
+    pub vim: bool,
+    /// 所有文件类型共用的设置
+    pub editing: FileSettings,
+    /// 按文件类型名称(小写)覆盖的设置
+    filetypes: HashMap<String, FileOverrides>,
+}
+
+/// 界面颜色
+#[derive(Debug, Clone, Copy)]
+pub struct Colors {
+    pub status_bg: Rgb,
+    pub status_fg: Rgb,
+    pub selection_bg: Rgb,
+    pub line_number_fg: Rgb,
+}
+
+/// 与文件类型相关的设置
+#[derive(Debug, Clone, Copy)]
+pub struct FileSettings {
+    /// 制表符宽度(到下一个制表位的列数)
+    pub tab_width: usize,
+    /// 按Tab键时是否插入空格(到下一个制表位)而不是制表符
+    pub expand_tabs: bool,
+    /// 新建文件使用的换行符, 打开的文件保持原来的换行符
+    pub line_ending: LineEnding,
+    /// 是否语法高亮
+    pub highlighting: bool,
+}
+
+/// 某个文件类型覆盖的设置, 没有指定的设置使用共用的设置
+#[derive(Debug, Default, Clone, Copy)]
+struct FileOverrides {
+    tab_width: Option<usize>,
+    expand_tabs: Option<bool>,
+    line_ending: Option<LineEnding>,
+    highlighting: Option<bool>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            alternate_screen: true,
+            mouse: true,
+            welcome: true,
+            colors: Colors::default(),
+            keymap: Keymap::default(),
+            vim: false,
+            editing: FileSettings::default(),
+            filetypes: HashMap::new(),
+        }
+    }
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            status_bg: constants::STATUS_BG_COLOR,
+            status_fg: constants::STATUS_FG_COLOR,
+            selection_bg: constants::SELECTION_BG_COLOR,
+            line_number_fg: constants::LINE_NUMBER_FG_COLOR,
+        }
+    }
+}
+
+impl Default for FileSettings {
+    fn default() -> Self {
+        Self {
+            tab_width: constants::TAB_WIDTH,
+            expand_tabs: false,
+            line_ending: LineEnding::default(),
+            highlighting: true,
+        }
+    }
+}
+
+impl Config {
+    /// 读取设置, 返回设置以及配置文件中的错误(无效的设置被忽略, 使用默认值)
+    /// 之后读取环境变量: LECTO_ALTERNATE_SCREEN为0, false或off时不使用备用屏幕, LECTO_MOUSE为0, false或off时不启用鼠标
+    pub fn load() -> (Self, Vec<String>) {
+        let mut config = Self::default();
+        let mut errors = Vec::new();
+        if let Some(path) = Config::path() {
+            match fs::read_to_string(&path) {
+                Ok(contents) => config.apply(&contents, &mut errors),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => errors.push(format!("could not read {}: {}", path.display(), err)),
+            }
+        }
+        if let Some(value) = Config::env_flag("LECTO_ALTERNATE_SCREEN") {
+            config.alternate_screen = value;
+        }
+        if let Some(value) = Config::env_flag("LECTO_MOUSE") {
+            config.mouse = value;
+        }
+        (config, errors)
+    }
+
+    /// 从配置文件的内容创建设置, 返回设置以及其中的错误(无效的设置被忽略, 使用默认值)
+    /// 不读取环境变量, 用于嵌入编辑器或测试时使用与配置文件相同的格式指定设置
+    pub fn from_toml(contents: &str) -> (Self, Vec<String>) {
+        let mut config = Self::default();
+        let mut errors = Vec::new();
+        config.apply(contents, &mut errors);
+        (config, errors)
+    }
+
+    /// 指定文件类型(文件类型名称, 不区分大小写)的设置
+    pub fn settings_for(&self, file_type: &str) -> FileSettings {
+        let mut settings = self.editing;
+        if let Some(overrides) = self.filetypes.get(&file_type.to_lowercase()) {
+            overrides.apply_to(&mut settings);
+        }
+        settings
+    }
+
+    /// 配置文件的路径
+    fn path() -> Option<PathBuf> {
+        let directory = match env::var_os("XDG_CONFIG_HOME").filter(|value| !value.is_empty()) {
+            Some(directory) => PathBuf::from(directory),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(directory.join("lecto").join("config.toml"))
+    }
+
+    /// 解析配置文件的内容, 错误记录到errors中
+    fn apply(&mut self, contents: &str, errors: &mut Vec<String>) {
+        let table = match contents.parse::<Table>() {
+            Ok(table) => table,
+            Err(err) => {
+                let line = err
+                    .span()
+                    .map_or(1, |span| contents[..span.start].matches('\n').count() + 1);
+                errors.push(format!(
+                    "line {}: {}",
+                    line,
+                    err.message().replace('\n', ", ")
+                ));
+                return;
+            }
+        };
+        let mut editing = FileOverrides::default();
+        let mut keys = None;
+        for (key, value) in &table {
+            let result = match key.as_str() {
+                "keymap" => read_preset(value).map(|preset| self.keymap = Keymap::preset(preset)),
+                "keys" => {
+                    keys = Some(value);
+                    Ok(())
+                }
+                "alternate_screen" => read_bool(value).map(|value| self.alternate_screen = value),
+                "mouse" => read_bool(value).map(|value| self.mouse = value),
+                "welcome" => read_bool(value).map(|value| self.welcome = value),
+                "vim" => read_bool(value).map(|value| self.vim = value),
+                "colors" => {
+                    self.apply_colors(value, errors);
+                    Ok(())
+                }
+                "filetype" => {
+                    self.apply_filetypes(value, errors);
+                    Ok(())
+                }
+                _ => editing.read(key, value),
+            };
+            if let Err(message) = result {
+                errors.push(format!("{}: {}", key, message));
+            }
+        }
+        editing.apply_to(&mut self.editing);
+        // 在预设的基础上修改, 与两者在文件中的先后顺序无关
+        if let Some(keys) = keys {
+            self.apply_keys(keys, errors);
+        }
+    }
+
+    /// 解析[keys]
+    fn apply_keys(&mut self, value: &Value, errors: &mut Vec<String>) {
+        let Some(table) = value.as_table() else {
+            errors.push("keys: expected a table".to_string());
+            return;
+        };
+        for (keys, command) in table {
+            let binding = keymap::parse_keys(keys).and_then(|keys| {
+                let command = match command.as_str() {
+                    Some("none") => None,
+                    Some(name) => Some(
+                        Command::from_name(name)
+                            .ok_or_else(|| format!("unknown command '{}'", name))?,
+                    ),
+                    None => return Err("expected a command name".to_string()),
+                };
+                Ok((keys, command))
+            });
+            match binding {
+                Ok((keys, command)) => self.keymap.bind(keys, command),
+                Err(message) => errors.push(format!("keys.\"{}\": {}", keys, message)),
+            }
+        }
+    }
+
+    /// 解析[colors]
+    fn apply_colors(&mut self, value: &Value, errors: &mut Vec<String>) {
+        let Some(table) = value.as_table() else {
+            errors.push("colors: expected a table".to_string());
+            return;
+        };
+        for (key, value) in table {
+            let color = match key.as_str() {
+                "status_bg" => &mut self.colors.status_bg,
+                "status_fg" => &mut self.colors.status_fg,
+                "selection_bg" => &mut self.colors.selection_bg,
+                "line_number_fg" => &mut self.colors.line_number_fg,
+                _ => {
+                    errors.push(format!("colors.{}: unknown setting", key));
+                    continue;
+                }
+            };
+            match read_color(value) {
+                Ok(value) => *color = value,
+                Err(message) => errors.push(format!("colors.{}: {}", key, message)),
+            }
+        }
+    }
+
+    /// 解析[filetype.<name>]
+    fn apply_filetypes(&mut self, value: &Value, errors: &mut Vec<String>) {
+        let Some(table) = value.as_table() else {
+            errors.push("filetype: expected a table".to_string());
+            return;
+        };
+        for (name, value) in table {
+            let Some(settings) = value.as_table() else {
+                errors.push(format!("filetype.{}: expected a table", name));
+                continue;
+            };
+            let mut overrides = FileOverrides::default();
+            for (key, value) in settings {
+                if let Err(message) = overrides.read(key, value) {
+                    errors.push(format!("filetype.{}.{}: {}", name, key, message));
+                }
+            }
+            self.filetypes.insert(name.to_lowercase(), overrides);
+        }
+    }
+
+    /// 读取开关类型的环境变量, 没有设置时返回None
+    fn env_flag(name: &str) -> Option<bool> {
+        let value = env::var(name).ok()?;
+        Some(!matches!(
+            value.trim().to_lowercase().as_str(),
+            "0" | "false" | "off" | "no"
+        ))
+    }
+}
+
+impl FileOverrides {
+    /// 读取一项与文件类型相关的设置
+    fn read(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            "tab_width" => self.tab_width = Some(read_tab_width(value)?),
+            "expand_tabs" => self.expand_tabs = Some(read_bool(value)?),
+            "line_ending" => self.line_ending = Some(read_line_ending(value)?),
+            "highlighting" => self.highlighting = Some(read_bool(value)?),
+            _ => return Err("unknown setting".to_string()),
+        }
+        Ok(())
+    }
+
+    /// 用指定了的设置覆盖settings
+    fn apply_to(&self, settings: &mut FileSettings) {
+        if let Some(tab_width) = self.tab_width {
+            settings.tab_width = tab_width;
+        }
+        if let Some(expand_tabs) = self.expand_tabs {
+            settings.expand_tabs = expand_tabs;
+        }
+        if let Some(line_ending) = self.line_ending {
+            settings.line_ending = line_ending;
+        }
+        if let Some(highlighting) = self.highlighting {
+            settings.highlighting = highlighting;
+        }
+    }
+}
+
+fn read_bool(value: &Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| "expected true or false".to_string())
+}
+
+/// 预设的按键绑定
+fn read_preset(value: &Value) -> Result<Preset, String> {
+    value
+        .as_str()
+        .and_then(Preset::from_name)
+        .ok_or_else(|| "expected \"default\", \"emacs\" or \"nano\"".to_string())
+}
+
+/// 制表符宽度, 1到16之间
+fn read_tab_width(value: &Value) -> Result<usize, String> {
+    value
+        .as_integer()
+        .filter(|width| (1..=16).contains(width))
+        .map(|width| width as usize)
+        .ok_or_else(|| "expected an integer between 1 and 16".to_string())
+}
+
+/// 换行符, "lf"或"crlf"
+fn read_line_ending(value: &Value) -> Result<LineEnding, String> {
+    match value.as_str().map(str::to_lowercase).as_deref() {
+        Some("lf") => Ok(LineEnding::Lf),
+        Some("crlf") => Ok(LineEnding::Crlf),
+        _ => Err("expected \"lf\" or \"crlf\"".to_string()),
+    }
+}
+
+/// 颜色, 格式为"#rrggbb"
+fn read_color(value: &Value) -> Result<Rgb, String> {
+    let error = || "expected a color like \"#rrggbb\"".to_string();
+    let hex = value
+        .as_str()
+        .and_then(|value| value.strip_prefix('#'))
+        .filter(|hex| hex.len() == 6 && hex.is_ascii())
+        .ok_or_else(error)?;
+    let channel =
+        |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).map_err(|_| error());
+    Ok(Rgb(channel(0)?, channel(2)?, channel(4)?))
+}
@@ -1,7 +1,20 @@
+use std::time::Duration;
 use termion::color;
 
 /// constants
-
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const TAB_WIDTH: usize = 4;
 pub const STATUS_BG_COLOR: color::Rgb = color::Rgb(239, 239, 239);
 pub const STATUS_FG_COLOR: color::Rgb = color::Rgb(63, 63, 63);
+pub const SELECTION_BG_COLOR: color::Rgb = color::Rgb(68, 90, 130);
+pub const LINE_NUMBER_FG_COLOR: color::Rgb = color::Rgb(120, 120, 120);
+/// 有未保存的修改时写入交换文件的最小间隔
+pub const SWAP_INTERVAL: Duration = Duration::from_secs(2);
+/// 文档每增加这么多字节, 交换文件的写入间隔增加一个SWAP_INTERVAL
+pub const SWAP_BACKOFF_LEN: usize = 1 << 20;
+/// 交换文件写入间隔的上限
+pub const MAX_SWAP_INTERVAL: Duration = Duration::from_secs(30);
+/// 检查文件是否被其他程序修改的间隔
+pub const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// 鼠标滚轮每次滚动的行数
+pub const MOUSE_SCROLL_LINES: usize = 3;
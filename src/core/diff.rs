@@ -0,0 +1,177 @@
+/// 差异中每个块前后保留的上下文行数
+const CONTEXT: usize = 3;
+/// 逐行比较时动态规划表的最大大小, 超过时把不同的部分整体视为删除后再插入
+const MAX_TABLE_SIZE: usize = 4_000_000;
+
+/// 比较结果中的一行
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// 生成统一格式(unified)的行差异, old_name和new_name用于文件头
+/// 比较时每行包括换行符, 因此只有换行符(LF/CRLF)或文件末尾的换行不同时也会给出差异
+/// 两者相同时返回空列表
+pub fn unified(old_name: &str, new_name: &str, old: &str, new: &str) -> Vec<String> {
+    let old: Vec<&str> = old.split_inclusive('\n').collect();
+    let new: Vec<&str> = new.split_inclusive('\n').collect();
+    let lines = compare(&old, &new);
+    if lines.iter().all(|line| matches!(line, Line::Same(_))) {
+        return Vec::new();
+    }
+
+    let mut result = vec![format!("--- {}", old_name), format!("+++ {}", new_name)];
+    // 找出所有不同的行, 前后相距不超过两倍上下文的合并为同一个块
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(_)))
+        .map(|(index, _)| index)
+        .collect();
+    let mut index = 0;
+    while index < changed.len() {
+        let start = changed[index].saturating_sub(CONTEXT);
+        let mut end = changed[index];
+        while index < changed.len() && changed[index] <= end + 2 * CONTEXT {
+            end = changed[index];
+            index += 1;
+        }
+        let end = (end + CONTEXT + 1).min(lines.len());
+        result.push(hunk_header(&lines, start, end));
+        for line in &lines[start..end] {
+            let (prefix, text) = match line {
+                Line::Same(text) => (' ', text),
+                Line::Removed(text) => ('-', text),
+                Line::Added(text) => ('+', text),
+            };
+            push_line(&mut result, prefix, text);
+        }
+    }
+    result
+}
+
+/// 输出一行: 去掉换行符, 行尾的\r显示为^M; 没有换行符的行(文件最后一行)之后与diff -u一样加上说明
+fn push_line(result: &mut Vec<String>, prefix: char, line: &str) {
+    let text = line.strip_suffix('\n').unwrap_or(line);
+    result.push(match text.strip_suffix('\r') {
+        Some(text) => format!("{}{}^M", prefix, text),
+        None => format!("{}{}", prefix, text),
+    });
+    if !line.ends_with('\n') {
+        result.push(String::from("\\ No newline at end of file"));
+    }
+}
+
+/// 块头: @@ -旧文件起始行,行数 +新文件起始行,行数 @@
+fn hunk_header(lines: &[Line], start: usize, end: usize) -> String {
+    let count = |lines: &[Line], old: bool| {
+        lines
+            .iter()
+            .filter(|line| match line {
+                Line::Same(_) => true,
+                Line::Removed(_) => old,
+                Line::Added(_) => !old,
+            })
+            .count()
+    };
+    let old_start = count(&lines[..start], true) + 1;
+    let new_start = count(&lines[..start], false) + 1;
+    format!(
+        "@@ -{},{} +{},{} @@",
+        old_start,
+        count(&lines[start..end], true),
+        new_start,
+        count(&lines[start..end], false)
+    )
+}
+
+/// 逐行比较(最长公共子序列), 先去掉相同的开头和结尾以减小比较范围
+fn compare<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut lines: Vec<Line> = old[..prefix].iter().map(|line| Line::Same(line)).collect();
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_TABLE_SIZE {
+        lines.extend(old_middle.iter().map(|line| Line::Removed(line)));
+        lines.extend(new_middle.iter().map(|line| Line::Added(line)));
+    } else {
+        lines.extend(longest_common_subsequence(old_middle, new_middle));
+    }
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| Line::Same(line)),
+    );
+    lines
+}
+
+/// 动态规划求最长公共子序列, 并据此生成差异
+fn longest_common_subsequence<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let width = new.len() + 1;
+    // table[i * width + j]为old[i..]与new[j..]的最长公共子序列长度
+    let mut table = vec![0usize; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i * width + j] = if old[i] == new[j] {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                table[(i + 1) * width + j].max(table[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len()
+            && (j == new.len() || table[(i + 1) * width + j] >= table[i * width + j + 1])
+        {
+            // 同一处既有删除又有插入时, 删除的行排在前面
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_ending_changes_are_shown() {
+        assert!(unified("a", "b", "x\ny\n", "x\ny\n").is_empty());
+        assert_eq!(
+            unified("a", "b", "x\ny\n", "x\r\ny\n"),
+            ["--- a", "+++ b", "@@ -1,2 +1,2 @@", "-x", "+x^M", " y"]
+        );
+        assert_eq!(
+            unified("a", "b", "x\ny\n", "x\ny"),
+            [
+                "--- a",
+                "+++ b",
+                "@@ -1,2 +1,2 @@",
+                " x",
+                "-y",
+                "+y",
+                "\\ No newline at end of file"
+            ]
+        );
+    }
+}
@@ -1,64 +1,234 @@
 use std::{
     fs,
-    io::{Error, Write},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{self, Write},
+    os::unix::{self, fs::MetadataExt},
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant, SystemTime},
 };
 
-use super::{editor::Position, row::Row};
+use super::{
+    constants, diff,
+    editor::{Position, SearchDirection},
+    encoding::Encoding,
+    error::Error,
+    filetype::FileType,
+    history::{History, Operation},
+    row::Row,
+    rows::Rows,
+    swap::Swap,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// 换行符风格
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// 换行符
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+    /// 显示名称
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+    /// 检测文本中使用的换行符, 混合使用时以数量多的为准(见is_mixed)
+    fn detect(contents: &str) -> Self {
+        let lines = contents.matches('\n').count();
+        let crlf = contents.matches("\r\n").count();
+        if crlf > lines - crlf {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        }
+    }
+    /// 文本中是否同时使用了LF和CRLF
+    fn is_mixed(contents: &str) -> bool {
+        let crlf = contents.matches("\r\n").count();
+        crlf > 0 && crlf < contents.matches('\n').count()
+    }
+}
+
+/// 文件在磁盘上的状态, 用于检测文件是否被其他程序修改
+/// 修改时间和大小都没有变化时认为文件没有变化, 否则再比较内容的哈希
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DiskState {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
 
-#[derive(Default, Debug)]
+impl DiskState {
+    /// 读取文件当前的状态, bytes为文件内容
+    fn of(path: &str, bytes: &[u8]) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            hash: hasher.finish(),
+        })
+    }
+}
+
+#[derive(Debug)]
 pub struct Document {
-    rows: Vec<Row>,
+    rows: Rows,
     dirty: bool,
+    history: History,
+    file_type: FileType,
+    line_ending: LineEnding,
+    saved_line_ending: LineEnding, // 打开或上次保存时的换行符, 切换回来后不再视为修改
+    mixed_line_endings: bool,      // 打开的文件混合使用了LF和CRLF, 保存时会统一为line_ending
+    trailing_newline: bool,        // 文件末尾是否有换行符
+    encoding: Encoding,
+    generation: usize,             // 每次修改内容(包括撤销/重做)时加一
+    swap_generation: usize,        // 上次写入(或删除)交换文件时的generation, 相同时不需要再写入
+    swap_written: Option<Instant>, // 上次写入交换文件的时间
+    swap_len: usize,               // 上次写入交换文件的字节数, 文档越大写入间隔越长
+    swap_kept: bool,               // 用户选择保留上次留下的交换文件, 本次编辑不写入也不删除交换文件
+    highlighted: usize,            // 开头已经高亮并且缓存仍然有效的行数, 高亮时从这里继续
+    disk_state: Option<DiskState>, // 打开或保存时文件在磁盘上的状态
     pub file_name: Option<String>,
 }
 
+impl Default for Document {
+    fn default() -> Self {
+        Self {
+            rows: Rows::default(),
+            dirty: false,
+            history: History::default(),
+            file_type: FileType::default(),
+            line_ending: LineEnding::default(),
+            saved_line_ending: LineEnding::default(),
+            mixed_line_endings: false,
+            // 新建的文件默认以换行符结尾
+            trailing_newline: true,
+            encoding: Encoding::default(),
+            generation: 0,
+            swap_generation: 0,
+            swap_written: None,
+            swap_len: 0,
+            swap_kept: false,
+            highlighted: 0,
+            disk_state: None,
+            file_name: None,
+        }
+    }
+}
+
 impl Document {
+    /// 新建一个空文档, 保存时使用指定的换行符
+    pub fn new(line_ending: LineEnding) -> Self {
+        Self {
+            line_ending,
+            saved_line_ending: line_ending,
+            ..Self::default()
+        }
+    }
+
     /// 打开一个文档
-    pub fn open(filename: &str) -> Result<Self, std::io::Error> {
-        let contents = fs::read_to_string(filename)?;
-        let mut rows = Vec::new();
+    /// 记录文件的编码, 使用的换行符以及末尾是否有换行符, 保存时保持原样
+    /// 二进制文件不会被打开, 返回BinaryFile错误
+    pub fn open(filename: &str) -> Result<Self, Error> {
+        let bytes = fs::read(filename)?;
+        let encoding = Encoding::detect(&bytes).ok_or(Error::BinaryFile)?;
+        Document::open_with_encoding(filename, &bytes, encoding)
+    }
 
-        for value in contents.lines() {
-            rows.push(Row::from(value));
+    /// 按指定编码重新读取文件(检测出的编码不正确时使用), 修改记录会被清空
+    pub fn reload_with_encoding(&mut self, encoding: Encoding) -> Result<(), Error> {
+        if let Some(file_name) = &self.file_name {
+            let bytes = fs::read(file_name)?;
+            *self = Document::open_with_encoding(file_name, &bytes, encoding)?;
         }
+        Ok(())
+    }
 
+    /// 按指定编码解码文件内容并构建文档
+    fn open_with_encoding(filename: &str, bytes: &[u8], encoding: Encoding) -> Result<Self, Error> {
+        let contents = encoding.decode(bytes)?;
+        let line_ending = LineEnding::detect(&contents);
         Ok(Self {
-            rows,
+            rows: Document::split_rows(&contents),
             file_name: Some(filename.to_string()),
             dirty: false,
+            history: History::default(),
+            file_type: FileType::from(filename),
+            line_ending,
+            saved_line_ending: line_ending,
+            mixed_line_endings: LineEnding::is_mixed(&contents),
+            trailing_newline: contents.ends_with('\n'),
+            encoding,
+            generation: 0,
+            swap_generation: 0,
+            swap_written: None,
+            swap_len: 0,
+            swap_kept: false,
+            highlighted: 0,
+            disk_state: DiskState::of(filename, bytes),
         })
     }
-    /// 插入新行
-    pub fn insert_new_line(&mut self, at: &Position) {
-        // 如果是最后一行直接追加
-        if at.y == self.len() {
-            self.rows.push(Row::default());
-            return;
-        }
 
-        let new_row = self.rows.get_mut(at.y).unwrap().split(at.x);
-        self.rows.insert(at.y + 1, new_row)
+    /// 新建一个没有文件名的只读展示用文档(例如差异), file_type决定高亮规则
+    pub fn scratch(lines: &[String], file_type: FileType) -> Self {
+        Self {
+            rows: Rows::from(
+                lines
+                    .iter()
+                    .map(|line| Row::from(line.as_str()))
+                    .collect::<Vec<Row>>(),
+            ),
+            file_type,
+            ..Self::default()
+        }
     }
 
+    /// 按行分割文本, 行尾的\r以及文本末尾的换行符不属于任何一行
+    fn split_rows(contents: &str) -> Rows {
+        let mut rows = Vec::new();
+        let body = contents.strip_suffix('\n').unwrap_or(contents);
+        if !contents.is_empty() {
+            for value in body.split('\n') {
+                rows.push(Row::from(value.strip_suffix('\r').unwrap_or(value)));
+            }
+        }
+        Rows::from(rows)
+    }
     /// 插入字符
     pub fn inesrt(&mut self, at: &Position, c: char) {
         // 大于文档长度
         if at.y > self.len() {
             return;
         }
-        self.dirty = true;
 
-        if c == '\n' {
-            self.insert_new_line(at);
-            return;
-        }
         if at.y == self.len() {
-            let mut row = Row::default();
-            row.insert(0, c);
-            self.rows.push(row)
+            self.ensure_row(at);
+            if c == '\n' {
+                return;
+            }
+        }
+
+        if c == '\n' {
+            self.execute(Operation::Split { at: *at });
         } else {
-            let row = self.rows.get_mut(at.y).unwrap();
-            row.insert(at.x, c);
+            self.execute(Operation::Insert {
+                at: *at,
+                text: c.to_string(),
+            });
         }
     }
     /// 删除字符
@@ -69,35 +239,575 @@ impl Document {
         if at.y >= len {
             return;
         }
-        self.dirty = true;
 
         // 光标在x轴是最后一个字符以及光标不是最后一行(光标是否在一行末尾及是否是最后一个字符 --> 行为空(x:0,len:0))
         // 事实上删除前会向前移动, 此时如果行首, 那么会跳到前一行
         // 假设前一行是空行且当前在{x:0,y:5,len:10}, 此时删除前会光标会是{x:0,y:4,len:0}, 此时下一行就会移动到上一行去
         // 假设前一行不是空行(len=8)且当前在{x:0,y:5,len:10},此时删除光标为{x:8(上一行的末尾位置等于长度),y:4,len:8}, 此时下一行也会移动到上一行去
-        if at.x == self.rows.get_mut(at.y).unwrap().len() && at.y < len - 1 {
-            let next_row = self.rows.remove(at.y + 1);
-            let row = self.rows.get_mut(at.y).unwrap();
-            row.append(&next_row)
+        let row_len = self.rows[at.y].len();
+        if at.x == row_len && at.y < len - 1 {
+            self.execute(Operation::Join { at: *at });
+        } else if at.x < row_len {
+            let text = self.rows[at.y].slice(at.x, 1);
+            self.execute(Operation::Delete { at: *at, text });
+        }
+    }
+
+    /// 获取start到end之间的文本, 跨行时以换行符连接
+    pub fn text_range(&self, start: &Position, end: &Position) -> String {
+        let mut text = String::new();
+        for y in start.y..=end.y {
+            let Some(row) = self.rows.get(y) else {
+                break;
+            };
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y { end.x } else { row.len() };
+            text.push_str(&row.slice(from, to.saturating_sub(from)));
+            if y != end.y {
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// 删除start到end之间的文本(跨行时会合并行)
+    pub fn delete_range(&mut self, start: &Position, end: &Position) {
+        if start.y >= self.len() {
+            return;
+        }
+        let end = if end.y >= self.len() {
+            let y = self.len() - 1;
+            Position {
+                x: self.rows[y].len(),
+                y,
+            }
+        } else {
+            *end
+        };
+        self.begin_group();
+        if start.y == end.y {
+            self.delete_in_row(start, end.x.saturating_sub(start.x));
+        } else {
+            // 先删除起始行光标之后的内容, 再依次删除后续行中被选中的部分并合并到起始行
+            self.delete_in_row(start, usize::MAX);
+            for y in start.y + 1..=end.y {
+                let next = Position {
+                    x: 0,
+                    y: start.y + 1,
+                };
+                let len = if y == end.y { end.x } else { usize::MAX };
+                self.delete_in_row(&next, len);
+                self.execute(Operation::Join { at: *start });
+            }
+        }
+        self.end_group();
+    }
+
+    /// 在at处插入文本(可以包含换行), 返回插入后文本末尾的位置
+    pub fn insert_text(&mut self, at: &Position, text: &str) -> Position {
+        if at.y > self.len() {
+            return *at;
+        }
+        self.begin_group();
+        self.ensure_row(at);
+        let mut position = *at;
+        for (index, line) in text.split('\n').enumerate() {
+            if index > 0 {
+                self.execute(Operation::Split { at: position });
+                position = Position {
+                    x: 0,
+                    y: position.y + 1,
+                };
+            }
+            if !line.is_empty() {
+                self.execute(Operation::Insert {
+                    at: position,
+                    text: line.to_string(),
+                });
+                position.x += line.graphemes(true).count();
+            }
+        }
+        self.end_group();
+        position
+    }
+
+    /// 替换文本
+    /// 将at处开始的len个字位替换为text
+    pub fn replace(&mut self, at: &Position, len: usize, text: &str) {
+        let Some(row) = self.rows.get(at.y) else {
+            return;
+        };
+        let removed = row.slice(at.x, len);
+        self.begin_group();
+        if !removed.is_empty() {
+            self.execute(Operation::Delete {
+                at: *at,
+                text: removed,
+            });
+        }
+        if !text.is_empty() {
+            self.execute(Operation::Insert {
+                at: *at,
+                text: text.to_string(),
+            });
+        }
+        self.end_group();
+    }
+
+    /// 撤销, 返回撤销后光标应处的位置
+    pub fn undo(&mut self) -> Option<Position> {
+        let step = self.history.undo()?;
+        let mut cursor = None;
+        for operation in step.iter().rev() {
+            cursor = Some(self.apply(&operation.inverse()));
+        }
+        self.update_dirty();
+        self.generation += 1;
+        cursor
+    }
+
+    /// 重做, 返回重做后光标应处的位置
+    pub fn redo(&mut self) -> Option<Position> {
+        let step = self.history.redo()?;
+        let mut cursor = None;
+        for operation in &step {
+            cursor = Some(self.apply(operation));
+        }
+        self.update_dirty();
+        self.generation += 1;
+        cursor
+    }
+
+    /// 结束当前的撤销步骤(例如移动光标后), 之后的输入不会再与之前的输入合并
+    pub fn seal_history(&mut self) {
+        self.history.seal()
+    }
+    /// 开始一个撤销步骤, 直到end_group之前的所有修改会被一次撤销
+    pub fn begin_group(&mut self) {
+        self.history.begin_group()
+    }
+    /// 结束撤销步骤
+    pub fn end_group(&mut self) {
+        self.history.end_group()
+    }
+
+    /// 光标在文档末尾的下一行(该行还不存在)时, 需要先在上一行末尾分割出新行
+    fn ensure_row(&mut self, at: &Position) {
+        if at.y != self.len() {
+            return;
+        }
+        if let Some(last) = self.rows.last() {
+            let at = Position {
+                x: last.len(),
+                y: at.y - 1,
+            };
+            self.execute(Operation::Split { at });
         } else {
-            let row = self.rows.get_mut(at.y).unwrap();
-            row.delete(at.x)
+            self.execute(Operation::CreateRow);
+        }
+    }
+
+    /// 删除一行中从at开始的len个字位(len超出行长度时删除到行尾)
+    fn delete_in_row(&mut self, at: &Position, len: usize) {
+        let text = self.rows[at.y].slice(at.x, len);
+        if !text.is_empty() {
+            self.execute(Operation::Delete { at: *at, text });
+        }
+    }
+
+    /// 执行并记录操作
+    fn execute(&mut self, operation: Operation) {
+        self.apply(&operation);
+        self.history.record(operation);
+        self.dirty = true;
+        self.generation += 1;
+    }
+
+    /// 执行操作(不记录), 返回操作完成后光标应处的位置
+    fn apply(&mut self, operation: &Operation) -> Position {
+        // 修改的行及之后的行需要重新高亮
+        let y = match operation {
+            Operation::Insert { at, .. }
+            | Operation::Delete { at, .. }
+            | Operation::Split { at }
+            | Operation::Join { at } => at.y,
+            Operation::CreateRow | Operation::RemoveRow => 0,
         };
+        self.highlighted = self.highlighted.min(y);
+        match operation {
+            Operation::Insert { at, text } => {
+                if at.y == self.rows.len() {
+                    self.rows.push(Row::default());
+                }
+                self.rows[at.y].insert(at.x, text);
+                Position {
+                    x: at.x + text.graphemes(true).count(),
+                    y: at.y,
+                }
+            }
+            Operation::Delete { at, text } => {
+                self.rows[at.y].delete(at.x, text.graphemes(true).count());
+                *at
+            }
+            Operation::Split { at } => {
+                let new_row = self.rows[at.y].split(at.x);
+                self.rows.insert(at.y + 1, new_row);
+                Position { x: 0, y: at.y + 1 }
+            }
+            Operation::Join { at } => {
+                let next_row = self.rows.remove(at.y + 1);
+                self.rows[at.y].append(next_row);
+                *at
+            }
+            Operation::CreateRow => {
+                self.rows.push(Row::default());
+                Position::default()
+            }
+            Operation::RemoveRow => {
+                self.rows.remove(0);
+                Position::default()
+            }
+        }
     }
 
     /// 保存修改后的文本
+    /// 先写入同一目录下的临时文件并同步到磁盘, 再重命名覆盖原文件, 保存过程中崩溃或磁盘写满都不会破坏原文件
+    /// 原文件是符号链接时写入链接指向的文件, 原文件的权限(以及可能的话属主)会保留到新文件上
     pub fn save(&mut self) -> Result<(), Error> {
         if let Some(file_name) = &self.file_name {
-            let mut file = fs::File::create(file_name)?;
-            for row in &self.rows {
-                file.write_all(row.as_bytes())?;
-                file.write_all(b"\n")?;
+            let bytes = self.encoding.encode(&self.contents())?;
+            let target = Document::resolve_target(Path::new(file_name))?;
+            Document::write_atomically(&target, &bytes)?;
+            self.disk_state = DiskState::of(file_name, &bytes);
+            self.history.mark_saved();
+            self.saved_line_ending = self.line_ending;
+            self.mixed_line_endings = false;
+            self.dirty = false;
+            self.remove_swap();
+        }
+        Ok(())
+    }
+
+    /// 按原来的编码重新读取文件, 未保存的修改会被丢弃
+    pub fn reload(&mut self) -> Result<(), Error> {
+        self.remove_swap();
+        self.reload_with_encoding(self.encoding)
+    }
+
+    /// 文件在打开或上次保存之后是否被其他程序修改过
+    /// 只有修改时间变化而内容没有变化时(如touch)更新记录的状态, 不视为修改; 文件被删除时也不视为修改(保存时重新创建)
+    pub fn changed_on_disk(&mut self) -> bool {
+        let (Some(file_name), Some(state)) = (&self.file_name, self.disk_state) else {
+            return false;
+        };
+        let Ok(metadata) = fs::metadata(file_name) else {
+            return false;
+        };
+        if metadata.modified().ok() == state.modified && metadata.len() == state.len {
+            return false;
+        }
+        let Ok(bytes) = fs::read(file_name) else {
+            return false;
+        };
+        match DiskState::of(file_name, &bytes) {
+            Some(current) if current.hash == state.hash => {
+                self.disk_state = Some(current);
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    /// 保留当前内容, 忽略文件在磁盘上的修改(之后保存时直接覆盖)
+    pub fn ignore_disk_change(&mut self) {
+        if let Some(file_name) = &self.file_name {
+            if let Ok(bytes) = fs::read(file_name) {
+                self.disk_state = DiskState::of(file_name, &bytes);
+            }
+        }
+    }
+
+    /// 磁盘上的文件与当前内容的差异(统一格式)
+    pub fn diff_disk(&self) -> Result<Vec<String>, Error> {
+        let name = self.file_name.clone().unwrap_or_default();
+        let disk = self.encoding.decode(&fs::read(&name)?)?;
+        Ok(diff::unified(
+            &format!("{} (disk)", name),
+            &format!("{} (buffer)", name),
+            &disk,
+            &self.contents(),
+        ))
+    }
+
+    /// 交换文件的路径(没有文件名或保留了上次的交换文件时为None)
+    fn swap_path(&self) -> Option<PathBuf> {
+        if self.swap_kept {
+            return None;
+        }
+        self.file_name.as_deref().map(Swap::path_for)
+    }
+
+    /// 保留上次留下的交换文件, 之后不再写入或删除该文件
+    pub fn keep_swap(&mut self) {
+        self.swap_kept = true;
+    }
+
+    /// 上次写入后有修改时更新交换文件, 修改全部撤销(与已保存的内容相同)时删除交换文件
+    /// 每次写入整个文档, 因此距上次写入的时间不足swap_interval时先不写入
+    pub fn write_swap(&mut self) -> Result<(), Error> {
+        if self.swap_generation == self.generation {
+            return Ok(());
+        }
+        let Some(path) = self.swap_path() else {
+            return Ok(());
+        };
+        if !self.dirty {
+            Swap::remove(&path)?;
+            self.swap_generation = self.generation;
+            return Ok(());
+        }
+        let interval = swap_interval(self.swap_len);
+        if self
+            .swap_written
+            .is_some_and(|written| written.elapsed() < interval)
+        {
+            return Ok(());
+        }
+        let contents = self.contents();
+        self.swap_written = Some(Instant::now());
+        self.swap_len = contents.len();
+        Swap::write(&path, &contents)
+            .map_err(|err| with_context(err, "could not write swap file", &path))?;
+        self.swap_generation = self.generation;
+        Ok(())
+    }
+
+    /// 删除交换文件(保存或放弃修改时)
+    pub fn remove_swap(&mut self) {
+        if let Some(path) = self.swap_path() {
+            let _ = Swap::remove(&path);
+        }
+        self.swap_generation = self.generation;
+    }
+
+    /// 读取上次异常退出时留下的交换文件, 内容与文档相同时直接删除并返回None
+    pub fn read_swap(&self) -> Option<Swap> {
+        let path = self.swap_path()?;
+        let swap = Swap::read(&path)?;
+        if swap.contents() == self.contents() && !swap.is_owner_running() {
+            let _ = Swap::remove(&path);
+            return None;
+        }
+        Some(swap)
+    }
+
+    /// 用交换文件中的内容替换文档内容, 恢复后的文档视为有未保存的修改
+    pub fn recover(&mut self, swap: &Swap) {
+        self.rows = Document::split_rows(swap.contents());
+        self.highlighted = 0;
+        self.trailing_newline = swap.contents().ends_with('\n');
+        self.history = History::default();
+        self.history.mark_unsaved();
+        self.dirty = true;
+        self.generation += 1;
+    }
+
+    /// 文档与交换文件内容的差异(统一格式)
+    pub fn diff_swap(&self, swap: &Swap) -> Vec<String> {
+        let name = self.file_name.clone().unwrap_or_default();
+        diff::unified(
+            &name,
+            &format!("{} (swap)", name),
+            &self.contents(),
+            swap.contents(),
+        )
+    }
+
+    /// 获取实际需要写入的路径(解析符号链接)
+    fn resolve_target(path: &Path) -> Result<PathBuf, io::Error> {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                // 链接目标不存在时canonicalize会失败, 此时按链接内容拼接出目标路径
+                fs::canonicalize(path).or_else(|_| {
+                    let link = fs::read_link(path)
+                        .map_err(|err| with_context(err, "could not read symlink", path))?;
+                    Ok(path.parent().unwrap_or(Path::new("")).join(link))
+                })
             }
-            self.dirty = false
+            _ => Ok(path.to_path_buf()),
+        }
+    }
+
+    /// 通过临时文件+重命名的方式写入文件
+    fn write_atomically(target: &Path, bytes: &[u8]) -> Result<(), io::Error> {
+        let directory = match target.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let name = target
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let temporary = directory.join(format!(".{}.lecto-{}.tmp", name, process::id()));
+
+        let result = Document::write_temporary(&temporary, target, bytes).and_then(|_| {
+            fs::rename(&temporary, target)
+                .map_err(|err| with_context(err, "could not replace", target))
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+            return result;
+        }
+        // 同步目录, 保证重命名本身也已经写入磁盘
+        if let Ok(directory) = fs::File::open(&directory) {
+            let _ = directory.sync_all();
         }
         Ok(())
     }
 
+    /// 写入临时文件并同步到磁盘, 同时复制原文件的权限和属主
+    fn write_temporary(temporary: &Path, target: &Path, bytes: &[u8]) -> Result<(), io::Error> {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(temporary)
+            .map_err(|err| with_context(err, "could not create temporary file", temporary))?;
+        file.write_all(bytes)
+            .map_err(|err| with_context(err, "could not write", temporary))?;
+        file.sync_all()
+            .map_err(|err| with_context(err, "could not sync", temporary))?;
+
+        if let Ok(metadata) = fs::metadata(target) {
+            fs::set_permissions(temporary, metadata.permissions())
+                .map_err(|err| with_context(err, "could not set permissions on", temporary))?;
+            // 没有权限修改属主时(例如编辑其他用户的文件)忽略错误
+            let _ = unix::fs::chown(temporary, Some(metadata.uid()), Some(metadata.gid()));
+        }
+        Ok(())
+    }
+
+    /// 按原有的换行符拼接所有行
+    fn contents(&self) -> String {
+        let mut contents = String::new();
+        for (y, row) in self.rows.iter().enumerate() {
+            if y > 0 {
+                contents.push_str(self.line_ending.as_str());
+            }
+            row.chunks().for_each(|chunk| contents.push_str(chunk));
+        }
+        if !self.rows.is_empty() && self.trailing_newline {
+            contents.push_str(self.line_ending.as_str());
+        }
+        contents
+    }
+
+    /// 从指定位置开始查找字符串, 返回匹配的位置
+    /// 向前查找时从at开始(包含at), 向后查找时从at之前开始(不包含at)
+    pub fn find(&self, query: &str, at: &Position, direction: SearchDirection) -> Option<Position> {
+        if at.y >= self.len() {
+            return None;
+        }
+        let mut position = *at;
+        let (start, end) = match direction {
+            SearchDirection::Forward => (at.y, self.len()),
+            SearchDirection::Backward => (0, at.y.saturating_add(1)),
+        };
+        for _ in start..end {
+            let row = self.rows.get(position.y)?;
+            if let Some(x) = row.find(query, position.x, direction) {
+                position.x = x;
+                return Some(position);
+            }
+            match direction {
+                SearchDirection::Forward => {
+                    position.y = position.y.saturating_add(1);
+                    position.x = 0;
+                }
+                SearchDirection::Backward => {
+                    position.y = position.y.saturating_sub(1);
+                    position.x = self.rows.get(position.y)?.len();
+                }
+            }
+        }
+        None
+    }
+
+    /// 修改文件名, 同时根据新的文件名确定文件类型
+    pub fn set_file_name(&mut self, file_name: Option<String>) {
+        self.file_type = file_name.as_deref().map(FileType::from).unwrap_or_default();
+        self.file_name = file_name;
+        // 文件类型变化后所有行都需要重新高亮
+        for row in self.rows.iter_mut() {
+            row.reset_highlighting();
+        }
+        self.highlighted = 0;
+    }
+
+    /// 换行符风格
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+    /// 修改换行符风格(保存时生效)
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.line_ending != line_ending {
+            self.line_ending = line_ending;
+            self.update_dirty();
+            self.generation += 1;
+        }
+    }
+    /// 打开的文件是否混合使用了LF和CRLF(保存之前), 保存时会全部改为line_ending
+    pub fn has_mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
+    /// 根据修改记录以及换行符是否与保存时相同更新修改状态
+    fn update_dirty(&mut self) {
+        self.dirty = !self.history.is_saved() || self.line_ending != self.saved_line_ending;
+    }
+    /// 文件末尾是否有换行符
+    pub fn has_trailing_newline(&self) -> bool {
+        self.trailing_newline
+    }
+    /// 文件编码
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+    /// 修改文件编码(保存时按新编码写入)
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        if self.encoding != encoding {
+            self.encoding = encoding;
+            self.history.mark_unsaved();
+            self.dirty = true;
+            self.generation += 1;
+        }
+    }
+
+    /// 文件类型
+    pub fn file_type(&self) -> &FileType {
+        &self.file_type
+    }
+
+    /// 语法高亮, until表示需要高亮到第几行(通常是屏幕最后一行)
+    /// 多行注释的状态需要从第一行开始依次传递, 从第一个缓存失效的行(上次修改的位置)继续, 之前的行不再检查
+    pub fn highlight(&mut self, until: usize) {
+        let until = until.min(self.len());
+        if self.highlighted >= until {
+            return;
+        }
+        let options = *self.file_type.highlighting_options();
+        let start = self.highlighted;
+        let mut in_comment = match start.checked_sub(1) {
+            Some(previous) => self.rows.get(previous).is_some_and(Row::ends_in_comment),
+            None => false,
+        };
+        for y in start..until {
+            if let Some(row) = self.rows.get_mut(y) {
+                in_comment = row.highlight(&options, in_comment);
+            }
+        }
+        self.highlighted = until;
+    }
+
     /// 获取指定行
     pub fn row(&self, index: usize) -> Option<&Row> {
         self.rows.get(index)
@@ -116,3 +826,113 @@ impl Document {
         self.rows.len()
     }
 }
+
+/// 交换文件的写入间隔, 每SWAP_BACKOFF_LEN字节增加一个SWAP_INTERVAL, 最长MAX_SWAP_INTERVAL
+fn swap_interval(len: usize) -> Duration {
+    let steps = u32::try_from(1 + len / constants::SWAP_BACKOFF_LEN).unwrap_or(u32::MAX);
+    constants::SWAP_INTERVAL
+        .saturating_mul(steps)
+        .min(constants::MAX_SWAP_INTERVAL)
+}
+
+/// 为IO错误添加上下文(操作及路径), 用于在消息栏中显示详细的错误信息
+fn with_context(error: io::Error, action: &str, path: &Path) -> io::Error {
+    io::Error::new(
+        error.kind(),
+        format!("{} {}: {}", action, path.display(), error),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::highlighting::Type;
+
+    /// 高亮整个文档, 返回每行第一个字位的高亮类型
+    fn first_types(document: &mut Document) -> Vec<Type> {
+        document.highlight(document.len());
+        (0..document.len())
+            .map(|y| {
+                let row = document.row(y).unwrap();
+                row.render(0, row.len(), 4)
+                    .first()
+                    .map_or(Type::None, |(highlighting_type, _)| *highlighting_type)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn highlight_resumes_from_the_edited_row() {
+        let lines: Vec<String> = ["let a = 1;", "/* b", "c */", "d"]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        let mut document = Document::scratch(&lines, FileType::from("a.rs"));
+        assert_eq!(
+            first_types(&mut document),
+            [Type::Keyword, Type::Comment, Type::Comment, Type::None]
+        );
+        assert_eq!(document.highlighted, 4);
+
+        // 删除注释开头后, 之后的行不再是注释
+        document.delete_range(&Position { x: 0, y: 1 }, &Position { x: 2, y: 1 });
+        assert_eq!(document.highlighted, 1);
+        assert_eq!(
+            first_types(&mut document),
+            [Type::Keyword, Type::None, Type::None, Type::None]
+        );
+
+        // 撤销后重新变为注释
+        document.undo();
+        assert_eq!(
+            first_types(&mut document),
+            [Type::Keyword, Type::Comment, Type::Comment, Type::None]
+        );
+
+        // 只高亮到屏幕底部时, 之后的行留到下次继续
+        document.inesrt(&Position { x: 0, y: 3 }, '/');
+        document.inesrt(&Position { x: 1, y: 3 }, '*');
+        document.highlight(2);
+        assert_eq!(document.highlighted, 3);
+        assert_eq!(first_types(&mut document)[3], Type::Comment);
+    }
+
+    #[test]
+    fn swap_is_written_only_after_new_changes() {
+        let dir = std::env::temp_dir().join(format!("lecto-swap-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file_name = dir.join("a.txt").to_string_lossy().into_owned();
+        let swap = Swap::path_for(&file_name);
+        let mut document = Document::scratch(&[String::from("a")], FileType::default());
+        document.file_name = Some(file_name);
+
+        document.inesrt(&Position { x: 1, y: 0 }, 'b');
+        document.write_swap().unwrap();
+        assert_eq!(Swap::read(&swap).unwrap().contents(), "ab\n");
+
+        // 没有新的修改时不再写入
+        fs::remove_file(&swap).unwrap();
+        document.write_swap().unwrap();
+        assert!(!swap.exists());
+
+        // 有新的修改, 但距上次写入还不到写入间隔
+        document.inesrt(&Position { x: 2, y: 0 }, 'c');
+        document.write_swap().unwrap();
+        assert!(!swap.exists());
+
+        document.swap_written = Some(Instant::now() - constants::SWAP_INTERVAL);
+        document.write_swap().unwrap();
+        assert_eq!(Swap::read(&swap).unwrap().contents(), "abc\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn swap_interval_grows_with_document_size() {
+        assert_eq!(swap_interval(0), constants::SWAP_INTERVAL);
+        assert_eq!(
+            swap_interval(3 * constants::SWAP_BACKOFF_LEN),
+            constants::SWAP_INTERVAL * 4
+        );
+        assert_eq!(swap_interval(usize::MAX), constants::MAX_SWAP_INTERVAL);
+    }
+}
@@ -1,20 +1,29 @@
 ///internal crate
 use super::{
+    backend::{Event, InputSource, MouseButton, MouseEvent, RenderTarget},
+    buffer::Buffer,
+    config::{Config, FileSettings},
     constants,
-    document::Document,
-    processor::Processor,
+    document::{Document, LineEnding},
+    encoding::Encoding,
+    error::Error,
+    filetype::FileType,
+    keymap::Command,
+    processor::{Action, FileArgument, KeyProcessor, Processor},
     row::Row,
     terminal::Terminal,
-    utils::{self, die},
+    utils,
+    vim::{self, InsertAt, Mode, Motion, NormalCommand, Operator, Parse, Range, Vim},
+    window::{Arrangement, Layout, Rect, Split, Window},
 };
+use regex::Regex;
 ///external crate
 use std::{
     cell::RefCell,
-    io::stdout,
+    mem,
     time::{Duration, Instant},
-    usize,
 };
-use termion::{event::Key, raw::IntoRawMode};
+use termion::event::Key;
 
 /// 编辑器中光标位置
 #[derive(Default, Clone, Copy, Debug)]
@@ -24,6 +33,12 @@ pub struct Position {
 }
 
 impl Position {
+    pub fn x(&self) -> usize {
+        self.x
+    }
+    pub fn y(&self) -> usize {
+        self.y
+    }
     pub fn set_position_x(&mut self, x: usize) {
         self.x = x;
     }
@@ -31,6 +46,48 @@ impl Position {
         self.y = y;
     }
 }
+/// 查找方向
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SearchDirection {
+    Forward,
+    Backward,
+}
+
+/// 行号显示模式
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum LineNumbers {
+    /// 不显示行号
+    #[default]
+    Off,
+    /// 绝对行号
+    Absolute,
+    /// 相对于光标所在行的行号
+    Relative,
+    /// 光标所在行显示绝对行号, 其他行显示相对行号
+    Hybrid,
+}
+
+impl LineNumbers {
+    /// 下一个模式(用于循环切换)
+    fn next(self) -> Self {
+        match self {
+            LineNumbers::Off => LineNumbers::Absolute,
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Hybrid,
+            LineNumbers::Hybrid => LineNumbers::Off,
+        }
+    }
+}
+
+/// 绘制一个窗口所需的状态
+struct View {
+    rect: Rect,
+    buffer: usize,
+    cursor_position: Position,
+    offset: Position,
+    active: bool, // 是否为当前窗口(只有当前窗口显示选区)
+}
+
 struct StatusMessage {
     text: String,
     time: Instant,
@@ -50,134 +107,978 @@ pub struct Editor {
     show_welcome: RefCell<bool>,
     cursor_position: RefCell<Position>, // 光标位置, 读文件时是光标在文本中的位置
     offset: RefCell<Position>,          // 读文件时文本在窗口中文档的偏移量
-    terminal: Terminal,
-    processor: Processor,
+    selection_anchor: RefCell<Option<Position>>, // 选择文本时的起点(锚点), 选区为锚点到光标之间的文本
+    dragging: RefCell<bool>,                     // 是否正在按住鼠标左键拖动选择
+    clipboard: RefCell<String>,                  // 内部剪贴板
+    line_numbers: RefCell<LineNumbers>,          // 行号显示模式
+    screen: Box<dyn RenderTarget>,               // 输出目标(终端或内存中的字符网格)
+    input: Box<dyn InputSource>,                 // 输入源(标准输入或预先给定的事件)
+    keys: KeyProcessor,                          // 按键绑定及组合键状态
     document: RefCell<Document>,
+    buffers: RefCell<Vec<Buffer>>, // 所有打开的缓冲区, 当前缓冲区的文档及光标状态保存在document, cursor_position, offset中
+    current_buffer: RefCell<usize>, // 当前缓冲区的下标
+    windows: RefCell<Vec<Window>>, // 所有窗口, 当前窗口的光标状态保存在cursor_position, offset中
+    layout: RefCell<Layout>,       // 窗口布局
+    current_window: RefCell<usize>, // 当前窗口的下标
     status_message: RefCell<StatusMessage>,
+    last_swap: RefCell<Instant>,       // 上次写入交换文件的时间
+    last_disk_check: RefCell<Instant>, // 上次检查文件是否被其他程序修改的时间
+    config: Config,                    // 设置(配置文件)
+    vim: Option<Vim>,                  // 模态编辑的状态, 没有启用时为None
 }
-impl Default for Editor {
-    fn default() -> Self {
+impl Editor {
+    /// 创建编辑器: 初始化终端并打开命令行中指定的文件, 终端无法初始化时返回错误
+    /// 配置文件中有无效的设置时在消息栏中提示
+    pub fn new() -> Result<Self, Error> {
+        let (config, errors) = Config::load();
+        let terminal = Terminal::new(&config)?;
+        let editor = Editor::build(
+            Box::new(Processor::default()),
+            Box::new(terminal),
+            Processor::read_files_for_command(),
+            config,
+        );
+        if !errors.is_empty() {
+            editor.rewrite_status_message(&format!("config.toml: {}", errors.join("; ")));
+        }
+        Ok(editor)
+    }
+
+    /// 使用指定的输入源和输出目标创建编辑器并打开指定的文件, 用于测试或在其他程序中嵌入编辑器
+    /// 例如使用ScriptedInput和MemoryScreen, 按预先给定的按键编辑文件并检查绘制出的画面
+    /// 使用默认设置, 不读取配置文件
+    pub fn with_backend(
+        input: Box<dyn InputSource>,
+        screen: Box<dyn RenderTarget>,
+        filenames: &[&str],
+    ) -> Self {
+        Editor::with_config(input, screen, filenames, Config::default())
+    }
+
+    /// 与with_backend相同, 但使用指定的设置(如启用模态编辑, 选择按键绑定的预设或按文件类型的设置)
+    pub fn with_config(
+        input: Box<dyn InputSource>,
+        screen: Box<dyn RenderTarget>,
+        filenames: &[&str],
+        config: Config,
+    ) -> Self {
+        let files = filenames
+            .iter()
+            .map(|filename| FileArgument {
+                filename: filename.to_string(),
+                position: None,
+            })
+            .collect();
+        Editor::build(input, screen, files, config)
+    }
+
+    fn build(
+        input: Box<dyn InputSource>,
+        screen: Box<dyn RenderTarget>,
+        files: Vec<FileArgument>,
+        config: Config,
+    ) -> Self {
+        let keys = KeyProcessor::new(config.keymap.clone());
         // 通过是否存在filename参数来构建不同的Document实例
-        let mut initial_status = String::from("HELP: Ctrl-Q=quit");
-        let document = if let Some(filename) = Processor::read_filename_for_command() {
-            let doc = Document::open(&filename);
-            if doc.is_ok() {
-                doc.unwrap()
-            } else {
-                initial_status = format!("Err Cloud not open file:{}", filename);
-                Document::default()
+        // 打开所有指定的文件, 无法打开的文件跳过, 所有失败的原因都显示在消息栏中
+        let mut documents = Vec::new();
+        let mut failures = Vec::new();
+        for argument in files {
+            match Editor::open_document(&argument.filename) {
+                Ok(document) => documents.push((document, argument.position)),
+                Err(message) => failures.push(message),
             }
-        } else {
-            Document::default()
+        }
+        let initial_status = match failures.len() {
+            0 => Editor::help_message(&keys),
+            1 => failures.remove(0),
+            count => format!("{} files were not opened: {}", count, failures.join("; ")),
         };
+        if documents.is_empty() {
+            documents.push((Document::new(config.editing.line_ending), None));
+        }
+        let positions: Vec<Option<Position>> =
+            documents.iter().map(|(_, position)| *position).collect();
+        let mut buffers: Vec<Buffer> = documents
+            .into_iter()
+            .map(|(document, _)| Buffer::new(document))
+            .collect();
+        let (document, _, _) = buffers[0].take();
 
-        Self {
+        let editor = Self {
             should_quit: RefCell::new(false),
-            show_welcome: RefCell::new(true),
+            show_welcome: RefCell::new(config.welcome),
             cursor_position: RefCell::new(Position::default()),
             offset: RefCell::new(Position::default()),
-            terminal: Terminal::default(),
-            processor: Processor::default(),
+            selection_anchor: RefCell::new(None),
+            dragging: RefCell::new(false),
+            clipboard: RefCell::new(String::new()),
+            line_numbers: RefCell::new(LineNumbers::default()),
+            screen,
+            input,
+            keys,
             status_message: RefCell::new(StatusMessage::from(initial_status)),
             document: RefCell::new(document),
+            buffers: RefCell::new(buffers),
+            current_buffer: RefCell::new(0),
+            windows: RefCell::new(vec![Window::default()]),
+            layout: RefCell::new(Layout::default()),
+            current_window: RefCell::new(0),
+            last_swap: RefCell::new(Instant::now()),
+            last_disk_check: RefCell::new(Instant::now()),
+            vim: config.vim.then(Vim::default),
+            config,
+        };
+        // 命令行中指定了行号(及列号)时跳转到对应位置
+        for (index, position) in positions.into_iter().enumerate().rev() {
+            editor.switch_buffer(index);
+            if let Some(position) = position {
+                editor.goto(position);
+            }
         }
+        editor
     }
-}
-impl Editor {
-    /// 启动编辑器
-    pub fn run(&mut self) {
-        let _stdout = stdout().into_raw_mode().unwrap();
+
+    /// 启动编辑器, 直到退出或发生无法恢复的错误(终端输出失败, 输入结束)
+    /// 出错时保留最后一帧画面(终端在释放时恢复), 使用MemoryScreen时可以在输入结束后检查画面
+    pub fn run(&mut self) -> Result<(), Error> {
+        // 依次检查打开的文件是否有交换文件, 最后回到第一个缓冲区
+        let len = self.buffers.borrow().len();
+        for index in (0..len).rev() {
+            self.switch_buffer(index);
+            self.recover_swap();
+        }
 
         loop {
-            if let Err(err) = self.refresh_editor_screen() {
-                self.terminal.clear_screen();
-                die(err)
-            }
+            self.refresh_editor_screen()?;
             if self.get_should_quit() {
-                self.terminal.clear_screen();
-                println!("Goodbye. \r");
-                break;
-            }
-            if let Err(err) = self
-                .processor
-                .process_keypress(|key| self.editor_processor(key))
-            {
-                utils::die(err)
+                self.remove_swap_files();
+                self.screen.clear_screen();
+                self.screen.draw_row("Goodbye. ");
+                return self.screen.flush();
             }
+            let event = self.input.read_event()?;
+            self.editor_processor(event);
         }
     }
     /// 文本编辑器处理案件的函数
-    fn editor_processor(&self, key: Key) {
-        match key {
-            Key::Ctrl('q') => self.exit(),
-            Key::Ctrl('s') => self.save(),
-            Key::Char(c) => {
+    fn editor_processor(&self, event: Event) {
+        // 欢迎信息一直显示到第一次按键(定期重绘以及挂起后重绘时仍然显示)
+        if matches!(event, Event::Key(_) | Event::Shift(_) | Event::Mouse(_)) {
+            self.set_show_welcome(false);
+        }
+        match event {
+            Event::Shift(key) => self.select(key),
+            Event::Key(key) => self.process_key(key),
+            Event::Mouse(mouse) => self.process_mouse(mouse),
+            Event::Resize => self.resize(),
+            Event::Suspend => self.suspend(),
+            Event::Tick => (),
+        }
+        // 按键之后使光标保持可见, 鼠标滚轮滚动窗口时光标可以不在窗口中
+        if matches!(event, Event::Key(_) | Event::Shift(_)) {
+            self.scroll();
+        }
+        self.write_swap_files();
+        self.check_external_change();
+    }
+
+    /// 读取按键, 等待按键期间窗口大小变化时立即重绘
+    fn read_key(&self) -> Result<Key, Error> {
+        loop {
+            match self.input.read_event()? {
+                Event::Key(key) => return Ok(key),
+                Event::Resize => {
+                    self.resize();
+                    self.refresh_editor_screen()?;
+                }
+                Event::Suspend => {
+                    self.suspend();
+                    self.refresh_editor_screen()?;
+                }
+                Event::Shift(_) | Event::Mouse(_) | Event::Tick => (),
+            }
+        }
+    }
+
+    /// 窗口大小变化: 更新终端大小, 调整偏移量使光标保持可见, 并清屏以便完整重绘
+    fn resize(&self) {
+        if self.screen.update_size().is_err() {
+            return;
+        }
+        self.scroll();
+        self.screen.clear_screen();
+    }
+
+    /// 挂起编辑器(Alt-Z或收到SIGTSTP), 在shell中执行fg继续后完整重绘
+    fn suspend(&self) {
+        if let Err(err) = self.screen.suspend() {
+            self.rewrite_status_message(&format!("Could not suspend: {}", err));
+        }
+        self.resize();
+    }
+
+    /// 帮助信息, 列出常用命令绑定的按键
+    fn help_message(keys: &KeyProcessor) -> String {
+        let commands = [
+            (Command::Find, "find"),
+            (Command::Replace, "replace"),
+            (Command::GotoLine, "goto"),
+            (Command::Undo, "undo"),
+            (Command::Redo, "redo"),
+            (Command::Copy, "copy"),
+            (Command::Cut, "cut"),
+            (Command::Paste, "paste"),
+            (Command::ChangeEncoding, "encoding"),
+            (Command::OpenFile, "open"),
+            (Command::ListBuffers, "buffers"),
+            (Command::SplitHorizontal, "split"),
+            (Command::Save, "save"),
+            (Command::Quit, "quit"),
+        ];
+        let help: Vec<String> = commands
+            .iter()
+            .filter_map(|(command, name)| {
+                keys.keys_for(*command)
+                    .map(|keys| format!("{}={}", keys, name))
+            })
+            .collect();
+        format!("HELP: {}", help.join(" | "))
+    }
+
+    /// 处理普通按键, 启用模态编辑时按照当前模式处理
+    fn process_key(&self, key: Key) {
+        match &self.vim {
+            Some(vim) => self.process_vim_key(vim, key),
+            None => self.process_bound_key(key),
+        }
+    }
+
+    /// 按照按键绑定执行命令, 没有绑定的字符插入到文档中
+    /// 输入组合键的前缀时在消息栏中显示已经输入的部分
+    fn process_bound_key(&self, key: Key) {
+        let was_pending = self.keys.is_pending();
+        let action = self.keys.process(key);
+        if was_pending && !matches!(action, Action::Pending(_)) {
+            self.rewrite_status_message("");
+        }
+        match action {
+            Action::Command(command) => self.execute(command),
+            Action::Insert('\t') if self.settings().expand_tabs => self.insert_tab_spaces(),
+            Action::Insert(c) => {
+                self.delete_selection();
                 self.insert_chat_at_document(c);
                 self.move_cursor(Key::Right)
             }
-            Key::Backspace => {
-                self.move_cursor(Key::Left);
-                self.delete_chat_at_document()
-            }
-            Key::Up
-            | Key::Down
-            | Key::Left
-            | Key::Right
-            | Key::PageUp
-            | Key::PageDown
-            | Key::End
-            | Key::Home => self.move_cursor(key),
+            Action::Pending(keys) => self.rewrite_status_message(&format!("{}-", keys)),
+            Action::Unbound(keys) => {
+                self.rewrite_status_message(&format!("{} is not bound to a command.", keys))
+            }
+        }
+    }
+
+    /// 执行命令
+    fn execute(&self, command: Command) {
+        match command {
+            Command::Quit => self.exit(),
+            Command::Save => self.save(),
+            Command::Find => self.search(),
+            Command::Replace => self.replace(),
+            Command::Undo => self.undo(),
+            Command::Redo => self.redo(),
+            Command::Copy => self.copy(),
+            Command::Cut => self.cut(),
+            Command::Paste => self.paste(),
+            Command::ToggleLineNumbers => self.toggle_line_numbers(),
+            Command::GotoLine => self.goto_line(),
+            Command::ToggleLineEnding => self.toggle_line_ending(),
+            Command::ChangeEncoding => self.change_encoding(),
+            Command::OpenFile => self.open_file(),
+            Command::ListBuffers => self.list_buffers(),
+            Command::NextBuffer => self.next_buffer(),
+            Command::PreviousBuffer => self.previous_buffer(),
+            Command::SplitHorizontal => self.split_window(Split::Horizontal),
+            Command::SplitVertical => self.split_window(Split::Vertical),
+            Command::CloseWindow => self.close_window(),
+            Command::NextWindow => self.next_window(),
+            Command::FocusLeft => self.focus_direction('h'),
+            Command::FocusDown => self.focus_direction('j'),
+            Command::FocusUp => self.focus_direction('k'),
+            Command::FocusRight => self.focus_direction('l'),
+            Command::Suspend => self.suspend(),
+            Command::Up => self.move_to(Key::Up),
+            Command::Down => self.move_to(Key::Down),
+            Command::Left => self.move_to(Key::Left),
+            Command::Right => self.move_to(Key::Right),
+            Command::PageUp => self.move_to(Key::PageUp),
+            Command::PageDown => self.move_to(Key::PageDown),
+            Command::LineStart => self.move_to(Key::Home),
+            Command::LineEnd => self.move_to(Key::End),
+            Command::Backspace => self.backspace(),
+            Command::Delete => self.delete_forward(),
+            Command::ClearSelection => self.clear_selection(),
+        }
+    }
+
+    /// 移动光标(取消选择)
+    fn move_to(&self, key: Key) {
+        self.clear_selection();
+        self.document.borrow_mut().seal_history();
+        self.move_cursor(key)
+    }
+
+    /// 启用模态编辑时处理按键
+    /// 普通模式和可视模式下没有对应命令的按键(如Ctrl-S)仍然按照按键绑定执行, 之后光标停在字符上
+    fn process_vim_key(&self, vim: &Vim, key: Key) {
+        match vim.mode() {
+            Mode::Insert => self.vim_insert_key(vim, key),
+            Mode::Visual => self.vim_visual_key(vim, key),
+            Mode::Normal | Mode::CommandLine => self.vim_normal_key(vim, key),
+        }
+        if vim.mode() != Mode::Insert {
+            self.clamp_vim_cursor();
+        }
+    }
+
+    /// 插入模式: Esc回到普通模式, 其他按键按照按键绑定处理(字符插入到文档中)
+    fn vim_insert_key(&self, vim: &Vim, key: Key) {
+        if key == Key::Esc {
+            self.document.borrow_mut().end_group();
+            vim.finish_insert();
+            // 与Vim一样, 回到普通模式时光标左移一个字符
+            let x = self.get_cursor_position().x.saturating_sub(1);
+            self.cursor_position.borrow_mut().set_position_x(x);
+            return;
+        }
+        if matches!(key, Key::Char(_) | Key::Backspace | Key::Delete) {
+            vim.record_insert(key);
+        }
+        self.process_bound_key(key);
+    }
+
+    /// 普通模式: 输入完整的命令后执行
+    fn vim_normal_key(&self, vim: &Vim, key: Key) {
+        // 组合键还没有输入完整时继续按照按键绑定处理
+        if self.keys.is_pending() {
+            self.process_bound_key(key);
+            return;
+        }
+        match vim.push_key(key) {
+            Parse::Complete(count, command) => self.vim_execute(vim, count, command),
+            Parse::Pending => (),
+            Parse::Invalid if !matches!(key, Key::Char(_)) => self.process_bound_key(key),
+            Parse::Invalid => (),
+        }
+    }
+
+    /// 可视模式: 移动光标扩展选区, d/x, c, y作用于选区(包含光标处的字符), Esc或v回到普通模式
+    fn vim_visual_key(&self, vim: &Vim, key: Key) {
+        let operator = match key {
+            Key::Char('d' | 'x') | Key::Delete => Operator::Delete,
+            Key::Char('c') => Operator::Change,
+            Key::Char('y') => Operator::Yank,
+            Key::Esc | Key::Char('v') => {
+                vim.clear_pending();
+                self.clear_selection();
+                vim.set_mode(Mode::Normal);
+                return;
+            }
+            _ => {
+                match vim.push_key(key) {
+                    Parse::Complete(count, NormalCommand::Move(motion)) => {
+                        self.vim_move(motion, count)
+                    }
+                    Parse::Invalid if !matches!(key, Key::Char(_)) => self.process_bound_key(key),
+                    _ => (),
+                }
+                return;
+            }
+        };
+        vim.clear_pending();
+        let cursor = self.get_cursor_position();
+        let anchor = self.selection_anchor.borrow().unwrap_or(cursor);
+        self.clear_selection();
+        let (start, mut end) = if (anchor.y, anchor.x) < (cursor.y, cursor.x) {
+            (anchor, cursor)
+        } else {
+            (cursor, anchor)
+        };
+        end.x = end.x.saturating_add(1).min(self.row_len(end.y));
+        vim.set_mode(Mode::Normal);
+        let lines = end.y - start.y;
+        let columns = if lines == 0 { end.x - start.x } else { end.x };
+        *self.cursor_position.borrow_mut() = start;
+        self.vim_execute(
+            vim,
+            None,
+            NormalCommand::OperateSelection {
+                operator,
+                lines,
+                columns,
+            },
+        );
+    }
+
+    /// 执行普通模式的命令, 修改文档的命令记录下来用于.重复
+    fn vim_execute(&self, vim: &Vim, count: Option<usize>, command: NormalCommand) {
+        let times = count.unwrap_or(1);
+        self.document.borrow_mut().seal_history();
+        match command {
+            NormalCommand::Move(motion) => self.vim_move(motion, count),
+            NormalCommand::Operate(operator, motion) => {
+                let range = vim::range(
+                    &self.document.borrow(),
+                    self.get_cursor_position(),
+                    operator,
+                    motion,
+                    count,
+                );
+                self.vim_apply(vim, operator, range);
+            }
+            NormalCommand::OperateSelection {
+                operator,
+                lines,
+                columns,
+            } => {
+                let range = vim::selection_range(
+                    &self.document.borrow(),
+                    self.get_cursor_position(),
+                    lines,
+                    columns,
+                );
+                self.vim_apply(vim, operator, range);
+            }
+            NormalCommand::DeleteChar => {
+                let range = vim::range(
+                    &self.document.borrow(),
+                    self.get_cursor_position(),
+                    Operator::Delete,
+                    Some(Motion::Right),
+                    count,
+                );
+                self.vim_apply(vim, Operator::Delete, range);
+            }
+            NormalCommand::Put { before } => self.vim_put(vim, before, times),
+            NormalCommand::Insert(at) => self.vim_insert(vim, at),
+            NormalCommand::Visual => {
+                *self.selection_anchor.borrow_mut() = Some(self.get_cursor_position());
+                vim.set_mode(Mode::Visual);
+            }
+            NormalCommand::Undo => (0..times).for_each(|_| self.undo()),
+            NormalCommand::Redo => (0..times).for_each(|_| self.redo()),
+            NormalCommand::Repeat => self.vim_repeat(vim, count),
+            NormalCommand::CommandLine => self.vim_command_line(vim),
+        }
+        if command.is_change() {
+            vim.record(count, command);
+        }
+    }
+
+    /// 按照动作移动光标, 上下移动时保持显示列不变
+    fn vim_move(&self, motion: Motion, count: Option<usize>) {
+        let times = count.unwrap_or(1);
+        match motion {
+            Motion::Up => (0..times).for_each(|_| self.move_cursor(Key::Up)),
+            Motion::Down => {
+                let last = self.document.borrow().len().saturating_sub(1);
+                for _ in 0..times {
+                    if self.get_cursor_position().y >= last {
+                        break;
+                    }
+                    self.move_cursor(Key::Down);
+                }
+            }
+            _ => {
+                let position = vim::target(
+                    &self.document.borrow(),
+                    self.get_cursor_position(),
+                    motion,
+                    count,
+                );
+                *self.cursor_position.borrow_mut() = position;
+            }
+        }
+    }
+
+    /// 对范围执行操作符: 复制到剪贴板, 删除(d)或删除后进入插入模式(c)
+    fn vim_apply(&self, vim: &Vim, operator: Operator, range: Range) {
+        let cursor = self.get_cursor_position();
+        let (text, start, end) = match range {
+            Range::Chars(start, end) => {
+                let text = self.document.borrow().text_range(&start, &end);
+                (text, start, end)
+            }
+            Range::Lines(first, last) => {
+                let end = Position {
+                    x: self.row_len(last),
+                    y: last,
+                };
+                let start = Position { x: 0, y: first };
+                let text = self.document.borrow().text_range(&start, &end);
+                (text, start, end)
+            }
+        };
+        // 空范围(例如空行上的x)不修改文档, 也不覆盖剪贴板
+        if text.is_empty() && matches!(range, Range::Chars(..)) {
+            if operator == Operator::Change {
+                self.vim_enter_insert(vim);
+                *self.cursor_position.borrow_mut() = start;
+            }
+            return;
+        }
+        *self.clipboard.borrow_mut() = text;
+        vim.set_linewise(matches!(range, Range::Lines(..)));
+        match (operator, range) {
+            (Operator::Yank, Range::Chars(..)) => *self.cursor_position.borrow_mut() = start,
+            (Operator::Yank, Range::Lines(first, _)) => {
+                *self.cursor_position.borrow_mut() = Position {
+                    x: cursor.x,
+                    y: first,
+                }
+            }
+            (Operator::Delete, Range::Chars(..)) => {
+                self.document.borrow_mut().delete_range(&start, &end);
+                *self.cursor_position.borrow_mut() = start;
+            }
+            (Operator::Delete, Range::Lines(first, last)) => {
+                self.delete_lines(first, last);
+                let document = self.document.borrow();
+                let y = first.min(document.len().saturating_sub(1));
+                let x = vim::first_non_blank(&document, y);
+                drop(document);
+                *self.cursor_position.borrow_mut() = Position { x, y };
+            }
+            (Operator::Change, _) => {
+                self.vim_enter_insert(vim);
+                // cc保留空行并在该行中输入
+                self.document.borrow_mut().delete_range(&start, &end);
+                *self.cursor_position.borrow_mut() = start;
+            }
+        }
+    }
+
+    /// 删除first到last之间的整行(包括换行), 文档中至少保留一行
+    fn delete_lines(&self, first: usize, last: usize) {
+        let len = self.document.borrow().len();
+        let end = Position {
+            x: self.row_len(last),
+            y: last,
+        };
+        let (start, end) = if last + 1 < len {
+            (Position { x: 0, y: first }, Position { x: 0, y: last + 1 })
+        } else if first > 0 {
+            let y = first - 1;
+            (
+                Position {
+                    x: self.row_len(y),
+                    y,
+                },
+                end,
+            )
+        } else {
+            (Position { x: 0, y: 0 }, end)
+        };
+        self.document.borrow_mut().delete_range(&start, &end);
+    }
+
+    /// 粘贴剪贴板中的文本times次, 整行的文本粘贴到当前行的下方(p)或上方(P)
+    fn vim_put(&self, vim: &Vim, before: bool, times: usize) {
+        let text = self.clipboard.borrow().clone();
+        if text.is_empty() {
+            return;
+        }
+        let cursor = self.get_cursor_position();
+        let mut document = self.document.borrow_mut();
+        document.begin_group();
+        let position = if vim.is_linewise() {
+            let lines = vec![text; times].join("\n");
+            let y = if before {
+                document.insert_text(&Position { x: 0, y: cursor.y }, &format!("{}\n", lines));
+                cursor.y
+            } else {
+                let at = Position {
+                    x: document.row(cursor.y).map_or(0, Row::len),
+                    y: cursor.y,
+                };
+                document.insert_text(&at, &format!("\n{}", lines));
+                cursor.y + 1
+            };
+            Position {
+                x: vim::first_non_blank(&document, y),
+                y,
+            }
+        } else {
+            let at = if before {
+                cursor
+            } else {
+                Position {
+                    x: cursor
+                        .x
+                        .saturating_add(1)
+                        .min(document.row(cursor.y).map_or(0, Row::len)),
+                    y: cursor.y,
+                }
+            };
+            // 光标停在粘贴的最后一个字符上
+            let end = document.insert_text(&at, &text.repeat(times));
+            Position {
+                x: end.x.saturating_sub(1),
+                y: end.y,
+            }
+        };
+        document.end_group();
+        drop(document);
+        *self.cursor_position.borrow_mut() = position;
+    }
+
+    /// 进入插入模式(i, a, I, A, o, O)
+    fn vim_insert(&self, vim: &Vim, at: InsertAt) {
+        let cursor = self.get_cursor_position();
+        let len = self.row_len(cursor.y);
+        self.vim_enter_insert(vim);
+        let position = match at {
+            InsertAt::Cursor => cursor,
+            InsertAt::After => Position {
+                x: cursor.x.saturating_add(1).min(len),
+                y: cursor.y,
+            },
+            InsertAt::LineStart => Position {
+                x: vim::first_non_blank(&self.document.borrow(), cursor.y),
+                y: cursor.y,
+            },
+            InsertAt::LineEnd => Position {
+                x: len,
+                y: cursor.y,
+            },
+            InsertAt::LineBelow => self.document.borrow_mut().insert_text(
+                &Position {
+                    x: len,
+                    y: cursor.y,
+                },
+                "\n",
+            ),
+            InsertAt::LineAbove => {
+                let at = Position { x: 0, y: cursor.y };
+                self.document.borrow_mut().insert_text(&at, "\n");
+                at
+            }
+        };
+        *self.cursor_position.borrow_mut() = position;
+    }
+
+    /// 进入插入模式, 直到回到普通模式之前的修改作为一个撤销步骤
+    fn vim_enter_insert(&self, vim: &Vim) {
+        self.document.borrow_mut().begin_group();
+        vim.set_mode(Mode::Insert);
+    }
+
+    /// 重复上一次修改, 指定了计数时替换原来的计数
+    fn vim_repeat(&self, vim: &Vim, count: Option<usize>) {
+        let Some(change) = vim.last_change() else {
+            return;
+        };
+        self.vim_execute(vim, count.or(change.count), change.command);
+        for key in change.inserted {
+            self.vim_insert_key(vim, key);
+        }
+        if vim.mode() == Mode::Insert {
+            self.vim_insert_key(vim, Key::Esc);
+        }
+    }
+
+    /// 命令行模式: 支持 `:w [file]`, `:q`, `:q!`, `:wq`, `:x`, `:e file` 以及 `:行号`
+    fn vim_command_line(&self, vim: &Vim) {
+        vim.set_mode(Mode::CommandLine);
+        let input = self.prompt(":", |_, _, _| {}).unwrap_or(None);
+        vim.set_mode(Mode::Normal);
+        let Some(input) = input else {
+            return;
+        };
+        let input = input.trim();
+        let (command, argument) = match input.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, Some(argument.trim())),
+            None => (input, None),
+        };
+        match (command, argument) {
+            ("", None) => (),
+            ("w", argument) => {
+                if let Some(filename) = argument {
+                    self.reset_document_filename(Some(filename.to_string()));
+                }
+                self.save();
+            }
+            ("q", None) => self.vim_quit(),
+            ("q!", None) => self.set_should_quit(true),
+            ("wq" | "x", None) => {
+                self.save();
+                self.vim_quit();
+            }
+            ("e", Some(filename)) => self.open_path(filename),
+            (line, None) if line.parse::<usize>().is_ok() => self.goto(Position {
+                x: 0,
+                y: line.parse::<usize>().unwrap_or(1).saturating_sub(1),
+            }),
+            _ => self.rewrite_status_message(&format!("Not an editor command: {}", input)),
+        }
+    }
+
+    /// :q 有未保存的缓冲区时不退出
+    fn vim_quit(&self) {
+        let dirty = self.dirty_buffer_names();
+        if dirty.is_empty() {
+            self.set_should_quit(true);
+        } else {
+            self.rewrite_status_message(&format!(
+                "No write since last change in {} (add ! to override)",
+                dirty.join(", ")
+            ));
+        }
+    }
+
+    /// 普通模式下光标停在字符上: 不超过最后一行, 也不在行尾之后
+    fn clamp_vim_cursor(&self) {
+        let last = self.document.borrow().len().saturating_sub(1);
+        let y = self.get_cursor_position().y.min(last);
+        let x = self
+            .get_cursor_position()
+            .x
+            .min(self.row_len(y).saturating_sub(1));
+        *self.cursor_position.borrow_mut() = Position { x, y };
+    }
+
+    /// 处理鼠标事件
+    /// 左键点击把光标移到点击位置(点击其他窗口时切换到该窗口), 按住左键拖动选择文本, 滚轮滚动鼠标所在的窗口(不移动光标)
+    /// 点击状态栏, 消息栏或分隔线时什么也不做
+    fn process_mouse(&self, event: MouseEvent) {
+        match event {
+            MouseEvent::Press(MouseButton::Left, x, y) => {
+                let (x, y) = (x.saturating_sub(1) as usize, y.saturating_sub(1) as usize);
+                let Some((index, rect)) = self.window_at(x, y) else {
+                    return;
+                };
+                self.focus_window(index);
+                self.clear_selection();
+                self.document.borrow_mut().seal_history();
+                let position = self.position_at(rect, x, y);
+                *self.cursor_position.borrow_mut() = position;
+                *self.selection_anchor.borrow_mut() = Some(position);
+                self.set_dragging(true);
+                self.scroll();
+            }
+            MouseEvent::Press(button @ (MouseButton::WheelUp | MouseButton::WheelDown), x, y) => {
+                let (x, y) = (x.saturating_sub(1) as usize, y.saturating_sub(1) as usize);
+                if let Some((index, _)) = self.window_at(x, y) {
+                    self.scroll_window(index, button == MouseButton::WheelDown);
+                }
+            }
+            MouseEvent::Hold(x, y) if self.get_dragging() => {
+                let (x, y) = (x.saturating_sub(1) as usize, y.saturating_sub(1) as usize);
+                let position = self.position_at(self.viewport(), x, y);
+                *self.cursor_position.borrow_mut() = position;
+                self.scroll();
+            }
+            MouseEvent::Release(..) => {
+                self.set_dragging(false);
+                // 只是点击而没有拖动时不保留锚点
+                if self.get_selection().is_none() {
+                    self.clear_selection();
+                }
+            }
             _ => (),
         }
-        self.scroll()
+    }
+
+    /// 屏幕位置所在的窗口及其区域
+    fn window_at(&self, x: usize, y: usize) -> Option<(usize, Rect)> {
+        self.arrange_windows()
+            .windows
+            .into_iter()
+            .find(|(_, rect)| rect.contains(x, y))
+    }
+
+    /// 屏幕位置对应的当前窗口中的文档位置, rect为当前窗口的区域
+    /// 位置在窗口上方或下方时为窗口之外的上一行或下一行(拖动选择时使窗口滚动), 在行号栏中时为行首, 超出行尾时为行尾
+    fn position_at(&self, rect: Rect, x: usize, y: usize) -> Position {
+        let offset = self.get_offset();
+        let y = if y < rect.y {
+            offset.y.saturating_sub(1)
+        } else if y >= rect.y + rect.height {
+            offset.y + rect.height
+        } else {
+            offset.y + y - rect.y
+        };
+        let tab_width = self.settings().tab_width;
+        let document = self.document.borrow();
+        let y = y.min(document.len());
+        let column = x.saturating_sub(rect.x + self.gutter_width_of(&document)) + offset.x;
+        let x = document
+            .row(y)
+            .map_or(0, |row| row.index_of(column, tab_width));
+        Position { x, y }
+    }
+
+    /// 滚动指定窗口的内容(不移动光标), down为true时向下滚动, 最多滚动到最后一行显示在窗口顶部
+    fn scroll_window(&self, index: usize, down: bool) {
+        let scrolled = |offset: Position, len: usize| Position {
+            y: if down {
+                (offset.y + constants::MOUSE_SCROLL_LINES).min(len.saturating_sub(1))
+            } else {
+                offset.y.saturating_sub(constants::MOUSE_SCROLL_LINES)
+            },
+            ..offset
+        };
+        if index == self.get_current_window() {
+            let offset = scrolled(self.get_offset(), self.document.borrow().len());
+            *self.offset.borrow_mut() = offset;
+            return;
+        }
+        let window = self.windows.borrow()[index];
+        let mut len = 0;
+        self.with_window_document(window.buffer(), |document| len = document.len());
+        self.windows.borrow_mut()[index] = Window::new(
+            window.buffer(),
+            window.cursor_position(),
+            scrolled(window.offset(), len),
+        );
     }
 
     /// 刷新文本编辑器屏幕
-    pub fn refresh_editor_screen(&self) -> Result<(), std::io::Error> {
-        self.terminal.cursor_hide();
-        self.terminal.cursor_position(&Position::default());
-        self.draw_start_running_symbol();
+    /// 依次绘制每个窗口及窗口之间的分隔线, 然后绘制状态栏和消息栏, 最后把光标放到当前窗口中
+    pub fn refresh_editor_screen(&self) -> Result<(), Error> {
+        let arrangement = self.arrange_windows();
+        self.screen.cursor_hide();
+        for (index, rect) in arrangement.windows {
+            let view = self.view_of(index, rect);
+            self.with_window_document(view.buffer, |document| {
+                // 高亮到窗口最后一行即可, 窗口之外的行等到显示时再高亮
+                if self.settings_of(document).highlighting {
+                    document.highlight(view.offset.y + rect.height);
+                }
+                self.draw_window(&view, document);
+            });
+        }
+        self.draw_separators(&arrangement.separators);
+        self.screen.cursor_position(&Position {
+            x: 0,
+            y: self.screen.size().height as usize,
+        });
         self.draw_status_bar();
         self.draw_message_bar();
         // 设置光标的位置, 此时光标和文本偏移绑定到一起了.
         // 假设正如scroll方法描述, 当向下碰到边界并越过时(第一次碰到)，此时y = 8, offset = 1,所以光标为 8 -1 = 7, 此时光标就是为最后一行位置. 如果一直就y=9 offset = 2,光标7,...
         // 假设正如scroll方法描述, 当向上碰到边界并越过时，此时y = 0, offset = 1,所以光标为 0 - 1 = 0 (saturating_sub), 此时光标是为第一行位置. 如果一直就y=0 offset = 0,光标0,...
-        let Position { x, y } = self.get_cursor_position();
-        self.terminal.cursor_position(&Position {
-            x: x.saturating_sub(self.get_offset().x),
-            y: y.saturating_sub(self.get_offset().y),
+        // 光标的x是字位下标, 需要转换为显示列(宽字符占两列, 制表符展开到制表位)
+        // 光标位置是相对于当前窗口的, 需要加上窗口在终端中的位置
+        let viewport = self.viewport();
+        let offset = self.get_offset();
+        let column = self.get_cursor_column();
+        let y = self.get_cursor_position().y;
+        self.screen.cursor_position(&Position {
+            x: column
+                .saturating_sub(offset.x)
+                .saturating_add(self.gutter_width())
+                .saturating_add(viewport.x),
+            y: y.saturating_sub(offset.y).saturating_add(viewport.y),
         });
-        self.terminal.cursor_show();
-        self.terminal.flush()
+        // 滚轮滚动后光标可能不在窗口中, 此时不显示光标, 下次按键时窗口滚动回光标所在的位置
+        if (offset.y..offset.y + viewport.height).contains(&y)
+            && (offset.x..offset.x + self.text_area_width()).contains(&column)
+        {
+            self.screen.cursor_show();
+        }
+        self.screen.flush()
     }
 
     /// 如果是文档，那么绘制文档行(这里文档指文件)
     /// 渲染文本的宽度(第一个字符=(offset.x=0)): 即start=0,end=0+width,渲染文本是render(0,width)=>text[0,min(width,text_len)], 得出结论: 要么终端长度要么文本长度. 正常显示
     /// 渲染文本的宽度(第五个字符=(offset.x=4)): 即start=4,end=4+width,渲染文本是render(4,4+width)=>text[4,min(4+width,text_len)],
-    pub fn draw_document_row(&self, row: &Row) {
-        let width = self.terminal.size().width as usize;
-        let x = self.get_offset().x;
-        let start = x;
-        let end = x + width;
-        let row = row.render(start, end);
-        self.terminal.draw_row(&row);
-    }
-
-    /// 绘制开始描述
-    pub fn draw_welcome_message(&self) {
-        self.terminal
-            .draw_row_text_center("~", "Lecto Editor & LiusNew");
-        self.terminal
-            .draw_row_text_center("~", "This is My Customer Editor By Rust");
-        self.terminal.draw_row_text_center("~", "\r");
-        self.terminal
-            .draw_row_text_center("~", &format!("Version: {}", constants::VERSION));
-        self.set_show_welcome(false);
+    /// offset.x以及start, end都是显示列, 而不是字位下标
+    /// index表示行在文档中的下标, 用于判断该行是否有被选中的部分, 被选中的部分使用选区背景色绘制(只有当前窗口显示选区)
+    /// width为文本区域的宽度, 行的内容不足时用空格补齐, 避免残留上次绘制的内容
+    /// tab_width为文档使用的制表符宽度
+    fn draw_document_row(
+        &self,
+        view: &View,
+        row: &Row,
+        index: usize,
+        width: usize,
+        tab_width: usize,
+    ) {
+        let start = view.offset.x;
+        let end = start + width;
+        let line_end = row.column_of(row.len(), tab_width);
+        let selection = if view.active {
+            self.selection_in_row(row, index)
+        } else {
+            None
+        };
+        let Some((selection_start, selection_end)) = selection else {
+            self.draw_highlighted(row, start, end, tab_width);
+            self.draw_padding(end - line_end.clamp(start, end));
+            return;
+        };
+        // 选区跨越行尾时, 行尾之后的一列表示被选中的换行
+        let selection_start = row.column_of(selection_start, tab_width).clamp(start, end);
+        let selection_end = if selection_end > row.len() {
+            line_end + 1
+        } else {
+            row.column_of(selection_end, tab_width)
+        }
+        .clamp(start, end);
+        self.draw_highlighted(row, start, selection_start, tab_width);
+        self.screen.set_bg_color(self.config.colors.selection_bg);
+        self.draw_highlighted(row, selection_start, selection_end, tab_width);
+        let mut drawn = line_end.clamp(start, end);
+        if selection_end > line_end && line_end >= start {
+            self.screen.draw_text(" ");
+            drawn += 1;
+        }
+        self.screen.reset_bg_color();
+        self.draw_highlighted(row, selection_end, end, tab_width);
+        self.draw_padding(end.saturating_sub(drawn.max(start)));
+    }
+
+    /// 绘制空白(用于补齐窗口宽度)
+    fn draw_padding(&self, width: usize) {
+        if width > 0 {
+            self.screen.draw_text(&" ".repeat(width));
+        }
+    }
+
+    /// 按高亮类型绘制行中start到end之间的文本
+    fn draw_highlighted(&self, row: &Row, start: usize, end: usize, tab_width: usize) {
+        for (highlighting_type, text) in row.render(start, end, tab_width) {
+            if let Some(color) = highlighting_type.to_color() {
+                self.screen.set_fg_color(color);
+                self.screen.draw_text(&text);
+                self.screen.reset_fg_color();
+            } else {
+                self.screen.draw_text(&text);
+            }
+        }
+    }
+
+    /// 绘制开始描述, line为欢迎信息中的第几行, 超出欢迎信息的行数时返回false
+    fn draw_welcome_message(&self, line: usize, width: usize) -> bool {
+        let version = format!("Version: {}", constants::VERSION);
+        let text = match line {
+            0 => "Lecto Editor & LiusNew",
+            1 => "This is My Customer Editor By Rust",
+            2 => "",
+            3 => &version,
+            _ => return false,
+        };
+        let padding = width.saturating_sub(utils::text_width(text)) / 2;
+        let line = format!("~{}{}", " ".repeat(padding.saturating_sub(1)), text);
+        let line = utils::truncate_to_width(&line, width);
+        self.screen.draw_text(&line);
+        self.draw_padding(width.saturating_sub(utils::text_width(&line)));
+        true
     }
     /// 绘制状态栏
     fn draw_status_bar(&self) {
         let mut status;
-        let width = self.terminal.size().width as usize;
+        let width = self.screen.size().width as usize;
         let document = self.document.borrow();
         let modified_indicator = if document.is_dirty() {
             "(modified)"
@@ -187,71 +1088,102 @@ impl Editor {
 
         let mut file_name = "[No Name]".to_string();
         if let Some(name) = &document.file_name {
-            file_name = name.clone();
-            file_name.truncate(20);
+            file_name = utils::truncate_to_width(name, 20);
         }
+        let buffers = self.buffers.borrow().len();
+        let buffer_indicator = if buffers > 1 {
+            format!(" [{}/{}]", self.get_current_buffer() + 1, buffers)
+        } else {
+            String::new()
+        };
+        // 启用模态编辑时在最左侧显示当前模式
+        let mode_indicator = match &self.vim {
+            Some(vim) => format!("-- {} -- ", vim.mode().name()),
+            None => String::new(),
+        };
         status = format!(
-            "{} - {} lines{}",
+            "{}{} - {} lines{}{}",
+            mode_indicator,
             file_name,
             &document.len(),
-            modified_indicator
+            modified_indicator,
+            buffer_indicator
+        );
+        let line_ending = format!(
+            "{}{}{}",
+            document.line_ending().name(),
+            if document.has_mixed_line_endings() {
+                " mixed"
+            } else {
+                ""
+            },
+            if document.has_trailing_newline() {
+                ""
+            } else {
+                " noeol"
+            }
         );
         let line_indicator = format!(
-            "{}/{}",
+            "{} | {} | {} | {}/{}",
+            document.file_type().name(),
+            document.encoding().name(),
+            line_ending,
             self.get_cursor_position().y.saturating_add(1),
             &document.len()
         );
-        let len = status.len() + line_indicator.len();
+        let len = utils::text_width(&status) + utils::text_width(&line_indicator);
 
-        if width > len {
-            status.push_str(&" ".repeat(width - len))
-        }
-        status = format!("{}{}", status, line_indicator);
-        status.truncate(width);
-        Terminal::set_bg_color(constants::STATUS_BG_COLOR);
-        Terminal::set_fg_color(constants::STATUS_FG_COLOR);
-        self.terminal.draw_row(&status);
-        Terminal::reset_fg_color();
-        Terminal::reset_bg_color();
+        // 宽度不够时至少保留一个空格分隔左右两部分
+        status.push_str(&" ".repeat(width.saturating_sub(len).max(1)));
+        status = utils::truncate_to_width(&format!("{}{}", status, line_indicator), width);
+        self.screen.set_bg_color(self.config.colors.status_bg);
+        self.screen.set_fg_color(self.config.colors.status_fg);
+        self.screen.draw_row(&status);
+        self.screen.reset_fg_color();
+        self.screen.reset_bg_color();
     }
 
     /// 绘制消息提示
     fn draw_message_bar(&self) {
-        self.terminal.clear_current_line();
+        self.screen.clear_current_line();
         let message = self.status_message.borrow();
         if Instant::now() - message.time < Duration::new(5, 0) {
-            let mut text = message.text.clone();
-            text.truncate(self.terminal.size().width as usize);
-            self.terminal.draw_row(&text);
+            let text = utils::truncate_to_width(&message.text, self.screen.size().width as usize);
+            self.screen.draw_row(&text);
         }
     }
     /// 让用户根据提示输入内容
-    fn prompt(&self, prompt: &str) -> Result<Option<String>, std::io::Error> {
+    /// callback: 每次按键后都会调用, 参数为按下的键以及当前输入的内容(用于实现增量查找等功能)
+    /// 按下Esc时返回None, 按下回车时返回输入内容(可能为空字符串)
+    fn prompt<C>(&self, prompt: &str, mut callback: C) -> Result<Option<String>, Error>
+    where
+        C: FnMut(&Self, Key, &String),
+    {
         let mut result = String::new();
+        let mut canceled = false;
         loop {
             self.rewrite_status_message(&format!("{}{}", prompt, result));
             self.refresh_editor_screen()?;
-            match Processor::read_key()? {
+            let key = self.read_key()?;
+            match key {
                 Key::Backspace => {
-                    if !result.is_empty() {
-                        result.truncate(result.len() - 1)
-                    }
+                    result.pop();
                 }
                 Key::Char('\n') => break,
-                Key::Char(c) => {
-                    if !c.is_control() {
-                        result.push(c)
-                    }
-                }
+                Key::Char(c) if !c.is_control() => result.push(c),
                 Key::Esc => {
                     result.truncate(0);
-                    break;
+                    canceled = true;
                 }
                 _ => (),
             }
+            callback(self, key, &result);
+            if canceled {
+                break;
+            }
         }
         self.rewrite_status_message("");
-        if result.is_empty() {
+        if canceled {
             return Ok(None);
         }
 
@@ -259,16 +1191,23 @@ impl Editor {
     }
 
     /// 退出
+    /// 有未保存的缓冲区时列出所有未保存的文件并确认
     fn exit(&self) {
-        if self.document.borrow().is_dirty() {
-            let exit_value = self.prompt("Exit(y/n): ").unwrap_or(None);
-            if let Some(exit_value) = exit_value {
-                if exit_value.to_lowercase() == "y" {
-                    self.set_should_quit(true)
-                }
+        let dirty = self.dirty_buffer_names();
+        if dirty.is_empty() {
+            self.set_should_quit(true);
+            return;
+        }
+        let exit_value = self
+            .prompt(
+                &format!("Unsaved changes in {}. Exit(y/n): ", dirty.join(", ")),
+                |_, _, _| {},
+            )
+            .unwrap_or(None);
+        if let Some(exit_value) = exit_value {
+            if exit_value.to_lowercase() == "y" {
+                self.set_should_quit(true)
             }
-        } else {
-            self.set_should_quit(true)
         }
     }
 
@@ -276,42 +1215,1019 @@ impl Editor {
     fn save(&self) {
         if self.get_documnet_filename().is_none() {
             // 获取名称, 默认为(None)
-            let new_name = self.prompt("Save as: ").unwrap_or(None);
+            let new_name = self
+                .prompt("Save as: ", |_, _, _| {})
+                .unwrap_or(None)
+                .filter(|name| !name.is_empty());
             if new_name.is_none() {
                 self.rewrite_status_message("Save aborted.");
                 return;
             }
-            self.reset_document_filename(new_name)
+            self.reset_document_filename(new_name);
+            // 新建的文件确定文件类型后使用该文件类型的换行符(已经手动切换过换行符时不改变)
+            let line_ending = self.settings().line_ending;
+            let mut document = self.document.borrow_mut();
+            if document.line_ending() == self.config.editing.line_ending {
+                document.set_line_ending(line_ending);
+            }
+        }
+        // 文件混合使用LF和CRLF时, 保存会统一换行符, 需要用户确认
+        let mixed = self.document.borrow().has_mixed_line_endings();
+        if mixed {
+            let line_ending = self.document.borrow().line_ending();
+            let answer = self
+                .prompt(
+                    &format!(
+                        "File has mixed line endings, save all lines with {}? (y/n): ",
+                        line_ending.name()
+                    ),
+                    |_, _, _| {},
+                )
+                .unwrap_or(None);
+            if answer.is_none_or(|answer| answer.to_lowercase() != "y") {
+                self.rewrite_status_message("Save aborted.");
+                return;
+            }
+        }
+        // 文件被其他程序修改过时先让用户确认, 避免覆盖其他程序的修改
+        match self.resolve_external_change() {
+            Some(true) => (),
+            Some(false) => return,
+            None => {
+                self.rewrite_status_message("Save aborted.");
+                return;
+            }
         }
         // 输出保存信息
-        let text = if self.save_document().is_ok() {
-            "File saved successfully.".to_string()
-        } else {
-            "Error writing file!".to_string()
+        let text = match self.save_document() {
+            Ok(()) => "File saved successfully.".to_string(),
+            Err(err) => format!("Error writing file: {}", err),
         };
         self.rewrite_status_message(&text)
     }
 
-    /// 文本编辑器打开后或运行时新行 绘制波浪线
-    pub fn draw_start_running_symbol(&self) {
-        let height = self.terminal.size().height;
+    /// 增量查找
+    /// 每次输入都会从当前光标处查找, 方向键用于跳转到下一个(右/下)或上一个(左/上)匹配位置
+    /// 按下Esc取消查找时, 恢复查找前的光标位置以及偏移量
+    fn search(&self) {
+        let old_position = self.get_cursor_position();
+        let old_offset = self.get_offset();
+        let query = self
+            .prompt(
+                "Search (ESC to cancel, Arrows to navigate): ",
+                |editor, key, query| {
+                    let mut moved = false;
+                    let direction = match key {
+                        Key::Right | Key::Down => {
+                            editor.move_cursor(Key::Right);
+                            moved = true;
+                            SearchDirection::Forward
+                        }
+                        Key::Left | Key::Up => SearchDirection::Backward,
+                        _ => SearchDirection::Forward,
+                    };
+                    let position = editor.document.borrow().find(
+                        query,
+                        &editor.get_cursor_position(),
+                        direction,
+                    );
+                    if let Some(position) = position {
+                        *editor.cursor_position.borrow_mut() = position;
+                        editor.scroll();
+                    } else if moved {
+                        editor.move_cursor(Key::Left);
+                    }
+                },
+            )
+            .unwrap_or(None);
+
+        if query.is_none() {
+            *self.cursor_position.borrow_mut() = old_position;
+            *self.offset.borrow_mut() = old_offset;
+            self.scroll();
+        }
+    }
+
+    /// 正则替换
+    /// 从文档开头开始查找匹配项, 替换内容支持捕获组($1, ${name}), 每个匹配项都需要用户确认
+    /// y: 替换当前项, n: 跳过当前项, a: 替换当前项及之后所有项, q/Esc: 结束替换
+    fn replace(&self) {
+        let pattern = match self.prompt("Replace (regex): ", |_, _, _| {}) {
+            Ok(Some(pattern)) if !pattern.is_empty() => pattern,
+            _ => {
+                self.rewrite_status_message("Replace aborted.");
+                return;
+            }
+        };
+        let regex = match Regex::new(&pattern) {
+            Ok(regex) => regex,
+            Err(err) => {
+                self.rewrite_status_message(&format!("Invalid pattern: {}", err));
+                return;
+            }
+        };
+        let replacement = match self.prompt("Replace with: ", |_, _, _| {}) {
+            Ok(Some(replacement)) => replacement,
+            _ => {
+                self.rewrite_status_message("Replace aborted.");
+                return;
+            }
+        };
+
+        let mut count = 0;
+        let mut replace_all = false;
+        let mut y = 0;
+        // 当前行中继续查找的字节位置
+        let mut from = 0;
+        // 整个替换过程作为一个撤销步骤
+        self.document.borrow_mut().begin_group();
+        'rows: while y < self.document.borrow().len() {
+            // 当前行的文本, 替换后同步修改, 不需要每次从行中重新复制
+            let mut text = self
+                .document
+                .borrow()
+                .row(y)
+                .map(Row::to_string)
+                .unwrap_or_default();
+            loop {
+                // 查找匹配项并展开替换内容, 匹配位置转换为字位下标
+                let found = regex.captures_at(&text, from).map(|caps| {
+                    let matched = caps.get(0).unwrap();
+                    let mut expanded = String::new();
+                    caps.expand(&replacement, &mut expanded);
+                    (matched.start(), matched.end(), expanded)
+                });
+                let Some((start, end, expanded)) = found else {
+                    break;
+                };
+                let (at, len) = {
+                    let document = self.document.borrow();
+                    let row = document.row(y).unwrap();
+                    let at = row.grapheme_index(start);
+                    (at, row.grapheme_index(end) - at)
+                };
+                self.cursor_position.borrow_mut().set_position_y(y);
+                self.cursor_position.borrow_mut().set_position_x(at);
+                self.scroll();
+
+                let mut accept = replace_all;
+                if !replace_all {
+                    self.rewrite_status_message("Replace? (y)es / (n)o / (a)ll / (q)uit");
+                    if self.refresh_editor_screen().is_err() {
+                        break 'rows;
+                    }
+                    match self.read_key() {
+                        Ok(Key::Char('y')) => accept = true,
+                        Ok(Key::Char('a')) => {
+                            accept = true;
+                            replace_all = true;
+                        }
+                        Ok(Key::Char('q')) | Ok(Key::Esc) | Err(_) => break 'rows,
+                        _ => (),
+                    }
+                }
+
+                from = if accept {
+                    let mut document = self.document.borrow_mut();
+                    // 替换的是完整的字位, 可能比匹配的范围更长, 替换后从未修改的剩余部分之前继续查找
+                    let (start_byte, end_byte) = {
+                        let row = document.row(y).unwrap();
+                        (row.byte_index(at), row.byte_index(at + len))
+                    };
+                    document.replace(&Position { x: at, y }, len, &expanded);
+                    text.replace_range(start_byte..end_byte, &expanded);
+                    count += 1;
+                    start_byte + expanded.len()
+                } else {
+                    end
+                };
+                // 空匹配时向后移动一个字符, 防止在同一位置死循环
+                if start == end {
+                    match text[from..].chars().next() {
+                        Some(c) => from += c.len_utf8(),
+                        None => break,
+                    }
+                }
+            }
+            y += 1;
+            from = 0;
+        }
+        self.document.borrow_mut().end_group();
+        self.rewrite_status_message(&format!("Replaced {} occurrence(s).", count));
+    }
+
+    /// 按住Shift移动光标, 扩展选区
+    fn select(&self, key: Key) {
+        if self.selection_anchor.borrow().is_none() {
+            *self.selection_anchor.borrow_mut() = Some(self.get_cursor_position());
+        }
+        self.document.borrow_mut().seal_history();
+        self.move_cursor(key);
+    }
+
+    /// 取消选择
+    fn clear_selection(&self) {
+        *self.selection_anchor.borrow_mut() = None;
+    }
+
+    /// 获取选区, 返回按先后顺序排列的起点和终点, 没有选中内容时返回None
+    fn get_selection(&self) -> Option<(Position, Position)> {
+        let anchor = (*self.selection_anchor.borrow())?;
+        let cursor = self.get_cursor_position();
+        if (anchor.y, anchor.x) == (cursor.y, cursor.x) {
+            return None;
+        }
+        if (anchor.y, anchor.x) < (cursor.y, cursor.x) {
+            Some((anchor, cursor))
+        } else {
+            Some((cursor, anchor))
+        }
+    }
+
+    /// 选区在指定行中的范围(字位下标), 选区包含行尾换行时终点为行长度加一
+    fn selection_in_row(&self, row: &Row, index: usize) -> Option<(usize, usize)> {
+        let (start, end) = self.get_selection()?;
+        if index < start.y || index > end.y {
+            return None;
+        }
+        let from = if index == start.y { start.x } else { 0 };
+        let to = if index == end.y {
+            end.x
+        } else {
+            row.len().saturating_add(1)
+        };
+        Some((from, to))
+    }
+
+    /// 删除选中的文本, 光标移动到选区起点. 没有选中内容时返回false
+    fn delete_selection(&self) -> bool {
+        let selection = self.get_selection();
+        self.clear_selection();
+        let Some((start, end)) = selection else {
+            return false;
+        };
+        self.document.borrow_mut().delete_range(&start, &end);
+        *self.cursor_position.borrow_mut() = start;
+        true
+    }
+
+    /// 复制选中的文本到剪贴板
+    fn copy(&self) {
+        if let Some((start, end)) = self.get_selection() {
+            *self.clipboard.borrow_mut() = self.document.borrow().text_range(&start, &end);
+            if let Some(vim) = &self.vim {
+                vim.set_linewise(false);
+            }
+            self.rewrite_status_message("Copied.");
+        }
+    }
+
+    /// 剪切选中的文本到剪贴板
+    fn cut(&self) {
+        if self.get_selection().is_some() {
+            self.copy();
+            self.delete_selection();
+            self.rewrite_status_message("Cut.");
+        }
+    }
+
+    /// 在光标处粘贴剪贴板中的文本(有选中内容时替换选中的文本)
+    fn paste(&self) {
+        let text = self.clipboard.borrow().clone();
+        if text.is_empty() {
+            return;
+        }
+        self.document.borrow_mut().begin_group();
+        self.delete_selection();
+        let position = self
+            .document
+            .borrow_mut()
+            .insert_text(&self.get_cursor_position(), &text);
+        self.document.borrow_mut().end_group();
+        *self.cursor_position.borrow_mut() = position;
+    }
+
+    /// 跳转到指定行, 输入格式为 `line` 或 `line:column`(从1开始)
+    fn goto_line(&self) {
+        let Some(input) = self
+            .prompt("Go to line[:column]: ", |_, _, _| {})
+            .unwrap_or(None)
+            .filter(|input| !input.trim().is_empty())
+        else {
+            return;
+        };
+        let mut parts = input.trim().splitn(2, ':');
+        let line = parts.next().map(str::parse::<usize>);
+        let column = parts.next().map(str::parse::<usize>).unwrap_or(Ok(1));
+        match (line, column) {
+            (Some(Ok(line)), Ok(column)) => self.goto(Position {
+                x: column.saturating_sub(1),
+                y: line.saturating_sub(1),
+            }),
+            _ => self.rewrite_status_message(&format!("Invalid line number: {}", input)),
+        }
+    }
+
+    /// 移动光标到指定位置(超出文档范围时限制在文档内), 并使目标行显示在屏幕中间
+    fn goto(&self, position: Position) {
+        {
+            let document = self.document.borrow();
+            let y = position.y.min(document.len().saturating_sub(1));
+            let x = document.row(y).map_or(0, |row| position.x.min(row.len()));
+            *self.cursor_position.borrow_mut() = Position { x, y };
+        }
+        self.clear_selection();
+        self.document.borrow_mut().seal_history();
+        self.scroll_to_center();
+    }
+
+    /// 在LF和CRLF之间切换换行符
+    fn toggle_line_ending(&self) {
+        let mut document = self.document.borrow_mut();
+        let line_ending = match document.line_ending() {
+            LineEnding::Lf => LineEnding::Crlf,
+            LineEnding::Crlf => LineEnding::Lf,
+        };
+        document.set_line_ending(line_ending);
+        drop(document);
+        self.rewrite_status_message(&format!("Line ending: {}", line_ending.name()));
+    }
+
+    /// 修改文件编码
+    /// 文档未修改时可以选择按新编码重新读取文件(用于检测出的编码不正确的情况), 否则保存时转换为新编码
+    fn change_encoding(&self) {
+        let names: Vec<&str> = Encoding::ALL
+            .iter()
+            .map(|encoding| encoding.name())
+            .collect();
+        let Some(input) = self
+            .prompt(&format!("Encoding ({}): ", names.join("/")), |_, _, _| {})
+            .unwrap_or(None)
+            .filter(|input| !input.trim().is_empty())
+        else {
+            return;
+        };
+        let Some(encoding) = Encoding::from_name(input.trim()) else {
+            self.rewrite_status_message(&format!("Unknown encoding: {}", input));
+            return;
+        };
+
+        let reloadable = {
+            let document = self.document.borrow();
+            !document.is_dirty() && document.file_name.is_some()
+        };
+        if reloadable {
+            let answer = self
+                .prompt(
+                    &format!("Reload file as {}? (y/n): ", encoding.name()),
+                    |_, _, _| {},
+                )
+                .unwrap_or(None);
+            if answer.is_some_and(|answer| answer.to_lowercase() == "y") {
+                let result = self.document.borrow_mut().reload_with_encoding(encoding);
+                match result {
+                    Ok(()) => {
+                        self.goto(self.get_cursor_position());
+                        self.rewrite_status_message(&format!("Reloaded as {}.", encoding.name()));
+                    }
+                    Err(err) => self.rewrite_status_message(&format!(
+                        "Could not reload as {}: {}",
+                        encoding.name(),
+                        err
+                    )),
+                }
+                return;
+            }
+        }
+        self.document.borrow_mut().set_encoding(encoding);
+        self.rewrite_status_message(&format!("Encoding: {} (applied on save)", encoding.name()));
+    }
+
+    /// 打开文件
+    /// 文件已经在某个缓冲区中打开时直接切换到该缓冲区
+    fn open_file(&self) {
+        let Some(filename) = self
+            .prompt("Open file: ", |_, _, _| {})
+            .unwrap_or(None)
+            .filter(|filename| !filename.trim().is_empty())
+        else {
+            return;
+        };
+        self.open_path(filename.trim());
+    }
+
+    /// 打开指定的文件
+    /// 文件已经在某个缓冲区中打开时直接切换到该缓冲区
+    fn open_path(&self, filename: &str) {
+        if let Some(index) = self.find_buffer(filename) {
+            self.switch_buffer(index);
+            return;
+        }
+        match Editor::open_document(filename) {
+            Ok(document) => {
+                let index = {
+                    let mut buffers = self.buffers.borrow_mut();
+                    buffers.push(Buffer::new(document));
+                    buffers.len() - 1
+                };
+                self.switch_buffer(index);
+                self.recover_swap();
+            }
+            Err(message) => self.rewrite_status_message(&message),
+        }
+    }
+
+    /// 打开文档, 失败时返回用于显示的错误信息
+    fn open_document(filename: &str) -> Result<Document, String> {
+        Document::open(filename).map_err(|err| match err {
+            Error::BinaryFile => format!(
+                "Warning: {} looks like a binary file and was not opened.",
+                filename
+            ),
+            err => format!("Error opening file {}: {}", filename, err),
+        })
+    }
+
+    /// 检查当前文档是否有上次异常退出时留下的交换文件, 有则让用户选择恢复, 查看差异, 丢弃或保留(Esc)
+    /// 另一个编辑器进程正在编辑该文件时只给出警告
+    fn recover_swap(&self) {
+        let Some(swap) = self.document.borrow().read_swap() else {
+            return;
+        };
+        let name = self.get_documnet_filename().unwrap_or_default();
+        if swap.is_owner_running() {
+            self.rewrite_status_message(&format!(
+                "Warning: {} is being edited by another lecto (pid {}).",
+                name,
+                swap.pid()
+            ));
+            return;
+        }
+        loop {
+            let Ok(answer) = self.prompt(
+                &format!(
+                    "Swap file found for {}. (r)ecover, (d)iff, (x) discard or Esc to keep: ",
+                    name
+                ),
+                |_, _, _| {},
+            ) else {
+                return;
+            };
+            match answer
+                .as_deref()
+                .map(|answer| answer.trim().to_lowercase())
+                .as_deref()
+            {
+                Some("r") => {
+                    self.document.borrow_mut().recover(&swap);
+                    self.goto(self.get_cursor_position());
+                    self.rewrite_status_message(&format!("Recovered {} from swap file.", name));
+                    return;
+                }
+                Some("x") => {
+                    self.document.borrow_mut().remove_swap();
+                    self.rewrite_status_message("Swap file discarded.");
+                    return;
+                }
+                Some("d") => {
+                    let lines = self.document.borrow().diff_swap(&swap);
+                    self.show_diff(&lines, "file (-) vs swap (+)");
+                }
+                // Esc: 保留交换文件, 打开磁盘上的文件, 本次编辑不覆盖交换文件
+                None => {
+                    self.document.borrow_mut().keep_swap();
+                    self.rewrite_status_message(&format!(
+                        "Swap file for {} kept; this session does not write a swap file.",
+                        name
+                    ));
+                    return;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// 在当前窗口中临时显示差异, 方向键滚动, 回车或Esc返回
+    fn show_diff(&self, lines: &[String], title: &str) {
+        let diff = Document::scratch(lines, FileType::from("changes.diff"));
+        let document = mem::replace(&mut *self.document.borrow_mut(), diff);
+        let cursor_position = mem::take(&mut *self.cursor_position.borrow_mut());
+        let offset = mem::take(&mut *self.offset.borrow_mut());
+
+        let _ = self.prompt(
+            &format!("Diff: {}. Arrows to scroll, Enter to return: ", title),
+            |editor, key, _| {
+                if matches!(
+                    key,
+                    Key::Up | Key::Down | Key::PageUp | Key::PageDown | Key::Left | Key::Right
+                ) {
+                    editor.move_cursor(key);
+                    editor.scroll();
+                }
+            },
+        );
+
+        *self.document.borrow_mut() = document;
+        *self.cursor_position.borrow_mut() = cursor_position;
+        *self.offset.borrow_mut() = offset;
+    }
+
+    /// 文件被其他程序修改时让用户选择重新读取, 保留当前内容或查看差异
+    /// 返回Some(true)表示保留当前内容, Some(false)表示已重新读取, None表示取消(按下Esc)
+    fn resolve_external_change(&self) -> Option<bool> {
+        if !self.document.borrow_mut().changed_on_disk() {
+            return Some(true);
+        }
+        let name = self.get_documnet_filename().unwrap_or_default();
+        loop {
+            let answer = self
+                .prompt(
+                    &format!(
+                        "{} changed on disk. (r)eload, (k)eep yours or (d)iff: ",
+                        name
+                    ),
+                    |_, _, _| {},
+                )
+                .unwrap_or(None)?;
+            match answer.trim().to_lowercase().as_str() {
+                "r" => {
+                    let result = self.document.borrow_mut().reload();
+                    match result {
+                        Ok(()) => {
+                            self.goto(self.get_cursor_position());
+                            self.rewrite_status_message(&format!("Reloaded {}.", name));
+                        }
+                        Err(err) => self
+                            .rewrite_status_message(&format!("Could not reload {}: {}", name, err)),
+                    }
+                    return Some(false);
+                }
+                "k" => {
+                    self.document.borrow_mut().ignore_disk_change();
+                    return Some(true);
+                }
+                "d" => {
+                    let result = self.document.borrow().diff_disk();
+                    match result {
+                        Ok(lines) => self.show_diff(&lines, "disk (-) vs buffer (+)"),
+                        Err(err) => self
+                            .rewrite_status_message(&format!("Could not read {}: {}", name, err)),
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// 距上次检查超过DISK_CHECK_INTERVAL时, 检查所有缓冲区的文件是否被其他程序修改, 取消时视为保留当前内容
+    /// 其他缓冲区的文件被修改时先切换到该缓冲区再询问, 之后回到原来的缓冲区
+    fn check_external_change(&self) {
+        if self.last_disk_check.borrow().elapsed() < constants::DISK_CHECK_INTERVAL {
+            return;
+        }
+        let current = self.get_current_buffer();
+        let len = self.buffers.borrow().len();
+        for index in (0..len).filter(|&index| index != current) {
+            let changed = self.buffers.borrow_mut()[index]
+                .document_mut()
+                .is_some_and(|document| document.changed_on_disk());
+            if changed {
+                self.switch_buffer(index);
+                if self.resolve_external_change().is_none() {
+                    self.document.borrow_mut().ignore_disk_change();
+                }
+            }
+        }
+        self.switch_buffer(current);
+        if self.resolve_external_change().is_none() {
+            self.document.borrow_mut().ignore_disk_change();
+        }
+        *self.last_disk_check.borrow_mut() = Instant::now();
+    }
+
+    /// 距上次写入超过SWAP_INTERVAL时, 为所有有新修改的缓冲区更新交换文件
+    fn write_swap_files(&self) {
+        if self.last_swap.borrow().elapsed() < constants::SWAP_INTERVAL {
+            return;
+        }
+        *self.last_swap.borrow_mut() = Instant::now();
+        let mut result = self.document.borrow_mut().write_swap();
+        for buffer in self.buffers.borrow_mut().iter_mut() {
+            if let Some(document) = buffer.document_mut() {
+                result = result.and(document.write_swap());
+            }
+        }
+        if let Err(err) = result {
+            self.rewrite_status_message(&format!("Error writing swap file: {}", err));
+        }
+    }
+
+    /// 退出时删除所有交换文件(未保存的修改已经确认放弃)
+    fn remove_swap_files(&self) {
+        self.document.borrow_mut().remove_swap();
+        for buffer in self.buffers.borrow_mut().iter_mut() {
+            if let Some(document) = buffer.document_mut() {
+                document.remove_swap();
+            }
+        }
+    }
+
+    /// 查找已经打开了指定文件的缓冲区
+    fn find_buffer(&self, filename: &str) -> Option<usize> {
+        let current = self.get_current_buffer();
+        if self.get_documnet_filename().as_deref() == Some(filename) {
+            return Some(current);
+        }
+        self.buffers.borrow().iter().position(|buffer| {
+            buffer
+                .document()
+                .is_some_and(|document| document.file_name.as_deref() == Some(filename))
+        })
+    }
+
+    /// 切换到指定的缓冲区, 保存当前缓冲区的光标位置和偏移量
+    fn switch_buffer(&self, index: usize) {
+        let current = self.get_current_buffer();
+        if index == current || index >= self.buffers.borrow().len() {
+            return;
+        }
+        self.clear_selection();
+        self.document.borrow_mut().seal_history();
+        let mut buffers = self.buffers.borrow_mut();
+        let document = mem::take(&mut *self.document.borrow_mut());
+        buffers[current].store(document, self.get_cursor_position(), self.get_offset());
+        let (document, cursor_position, offset) = buffers[index].take();
+        *self.document.borrow_mut() = document;
+        *self.cursor_position.borrow_mut() = cursor_position;
+        *self.offset.borrow_mut() = offset;
+        *self.current_buffer.borrow_mut() = index;
+    }
+
+    /// 切换到下一个缓冲区
+    fn next_buffer(&self) {
+        let len = self.buffers.borrow().len();
+        if len < 2 {
+            self.rewrite_status_message("Only one buffer open.");
+            return;
+        }
+        self.switch_buffer((self.get_current_buffer() + 1) % len);
+    }
+
+    /// 切换到上一个缓冲区
+    fn previous_buffer(&self) {
+        let len = self.buffers.borrow().len();
+        if len < 2 {
+            self.rewrite_status_message("Only one buffer open.");
+            return;
+        }
+        self.switch_buffer((self.get_current_buffer() + len - 1) % len);
+    }
+
+    /// 列出所有缓冲区, 输入序号切换
+    fn list_buffers(&self) {
+        let names: Vec<String> = self
+            .buffer_names()
+            .iter()
+            .enumerate()
+            .map(|(index, (name, dirty))| {
+                let name = format!("{}:{}{}", index + 1, name, if *dirty { "+" } else { "" });
+                if index == self.get_current_buffer() {
+                    format!("[{}]", name)
+                } else {
+                    name
+                }
+            })
+            .collect();
+        let Some(input) = self
+            .prompt(&format!("{} | Buffer: ", names.join(" ")), |_, _, _| {})
+            .unwrap_or(None)
+            .filter(|input| !input.trim().is_empty())
+        else {
+            return;
+        };
+        match input.trim().parse::<usize>() {
+            Ok(number) if (1..=names.len()).contains(&number) => self.switch_buffer(number - 1),
+            _ => self.rewrite_status_message(&format!("No such buffer: {}", input)),
+        }
+    }
+
+    /// 所有缓冲区的名称以及是否有未保存的修改
+    fn buffer_names(&self) -> Vec<(String, bool)> {
+        let current = self.get_current_buffer();
         let document = self.document.borrow();
-        for terminal_row in 0..height {
-            self.terminal.clear_current_line();
+        self.buffers
+            .borrow()
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| {
+                let document = if index == current {
+                    &document
+                } else {
+                    buffer.document().unwrap_or(&document)
+                };
+                let name = document
+                    .file_name
+                    .clone()
+                    .unwrap_or_else(|| "[No Name]".to_string());
+                (name, document.is_dirty())
+            })
+            .collect()
+    }
+
+    /// 有未保存修改的缓冲区名称
+    fn dirty_buffer_names(&self) -> Vec<String> {
+        self.buffer_names()
+            .into_iter()
+            .filter(|(_, dirty)| *dirty)
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// 计算所有窗口的区域以及分隔线的区域
+    fn arrange_windows(&self) -> Arrangement {
+        let size = self.screen.size();
+        self.layout.borrow().arrange(Rect {
+            x: 0,
+            y: 0,
+            width: size.width as usize,
+            height: size.height as usize,
+        })
+    }
+
+    /// 当前窗口的区域
+    fn viewport(&self) -> Rect {
+        let current = self.get_current_window();
+        self.arrange_windows()
+            .windows
+            .into_iter()
+            .find(|(index, _)| *index == current)
+            .map(|(_, rect)| rect)
+            .unwrap_or_default()
+    }
+
+    /// 绘制窗口所需的状态, 当前窗口的状态由Editor直接持有
+    fn view_of(&self, index: usize, rect: Rect) -> View {
+        if index == self.get_current_window() {
+            return View {
+                rect,
+                buffer: self.get_current_buffer(),
+                cursor_position: self.get_cursor_position(),
+                offset: self.get_offset(),
+                active: true,
+            };
+        }
+        let window = self.windows.borrow()[index];
+        View {
+            rect,
+            buffer: window.buffer(),
+            cursor_position: window.cursor_position(),
+            offset: window.offset(),
+            active: false,
+        }
+    }
+
+    /// 获取窗口显示的文档, 多个窗口显示同一个缓冲区时使用的是同一个文档
+    fn with_window_document<F>(&self, buffer: usize, call: F)
+    where
+        F: FnOnce(&mut Document),
+    {
+        if buffer != self.get_current_buffer() {
+            if let Some(document) = self
+                .buffers
+                .borrow_mut()
+                .get_mut(buffer)
+                .and_then(Buffer::document_mut)
+            {
+                call(document);
+                return;
+            }
+        }
+        call(&mut self.document.borrow_mut())
+    }
+
+    /// 分割当前窗口, 新窗口显示同一个缓冲区
+    fn split_window(&self, direction: Split) {
+        let viewport = self.viewport();
+        let too_small = match direction {
+            Split::Horizontal => viewport.height < 3,
+            Split::Vertical => viewport.width < 3,
+        };
+        if too_small {
+            self.rewrite_status_message("Window too small to split.");
+            return;
+        }
+        let current = self.get_current_window();
+        let mut windows = self.windows.borrow_mut();
+        windows.push(Window::new(
+            self.get_current_buffer(),
+            self.get_cursor_position(),
+            self.get_offset(),
+        ));
+        self.layout
+            .borrow_mut()
+            .split(current, windows.len() - 1, direction);
+        drop(windows);
+        self.scroll();
+        self.screen.clear_screen();
+    }
+
+    /// 关闭当前窗口, 焦点移到前一个窗口
+    fn close_window(&self) {
+        let current = self.get_current_window();
+        if self.windows.borrow().len() < 2 {
+            self.rewrite_status_message("Cannot close the last window.");
+            return;
+        }
+        self.windows.borrow_mut().remove(current);
+        self.layout.borrow_mut().remove(current);
+        self.load_window(current.saturating_sub(1));
+        self.screen.clear_screen();
+    }
+
+    /// 切换到下一个窗口
+    fn next_window(&self) {
+        let len = self.windows.borrow().len();
+        self.focus_window((self.get_current_window() + 1) % len);
+    }
+
+    /// 切换到指定方向(h/j/k/l: 左/下/上/右)上与光标最近的窗口
+    fn focus_direction(&self, direction: char) {
+        let viewport = self.viewport();
+        let cursor_x = viewport.x
+            + self
+                .get_cursor_column()
+                .saturating_sub(self.get_offset().x)
+                .saturating_add(self.gutter_width())
+                .min(viewport.width.saturating_sub(1));
+        let cursor_y = viewport.y
+            + self
+                .get_cursor_position()
+                .y
+                .saturating_sub(self.get_offset().y);
+        // 从光标所在位置沿指定方向越过窗口边界(及分隔线), 找到该位置所在的窗口
+        let windows = self.arrange_windows().windows;
+        let target = match direction {
+            'h' => viewport.x.checked_sub(2).map(|x| (x, cursor_y)),
+            'l' => Some((viewport.x + viewport.width + 1, cursor_y)),
+            'k' => viewport.y.checked_sub(2).map(|y| (cursor_x, y)),
+            _ => Some((cursor_x, viewport.y + viewport.height + 1)),
+        }
+        .and_then(|(x, y)| windows.iter().find(|(_, rect)| rect.contains(x, y)));
+        match target {
+            Some((index, _)) => self.focus_window(*index),
+            None => self.rewrite_status_message("No window in that direction."),
+        }
+    }
+
+    /// 切换到指定的窗口, 保存当前窗口的光标状态
+    fn focus_window(&self, index: usize) {
+        let current = self.get_current_window();
+        if index == current {
+            return;
+        }
+        self.windows.borrow_mut()[current] = Window::new(
+            self.get_current_buffer(),
+            self.get_cursor_position(),
+            self.get_offset(),
+        );
+        self.load_window(index);
+    }
+
+    /// 使指定窗口成为当前窗口, 切换到窗口显示的缓冲区并恢复光标状态
+    /// 其他窗口中的编辑可能使光标超出文档范围, 因此需要限制在文档内
+    fn load_window(&self, index: usize) {
+        let window = self.windows.borrow()[index];
+        self.switch_buffer(window.buffer());
+        *self.current_window.borrow_mut() = index;
+        let position = window.cursor_position();
+        let position = {
+            let document = self.document.borrow();
+            let y = position.y.min(document.len());
+            let x = document.row(y).map_or(0, |row| position.x.min(row.len()));
+            Position { x, y }
+        };
+        self.clear_selection();
+        *self.cursor_position.borrow_mut() = position;
+        *self.offset.borrow_mut() = window.offset();
+        self.scroll();
+    }
+
+    /// 撤销
+    fn undo(&self) {
+        let position = self.document.borrow_mut().undo();
+        match position {
+            Some(position) => *self.cursor_position.borrow_mut() = position,
+            None => self.rewrite_status_message("Nothing to undo."),
+        }
+    }
+
+    /// 重做
+    fn redo(&self) {
+        let position = self.document.borrow_mut().redo();
+        match position {
+            Some(position) => *self.cursor_position.borrow_mut() = position,
+            None => self.rewrite_status_message("Nothing to redo."),
+        }
+    }
+
+    /// 绘制一个窗口: 文档行以及文档之后的波浪线
+    fn draw_window(&self, view: &View, document: &Document) {
+        let gutter_width = self.gutter_width_of(document);
+        let text_width = view.rect.width.saturating_sub(gutter_width);
+        let tab_width = self.settings_of(document).tab_width;
+        let welcome_start = view.rect.height / 2;
+        for window_row in 0..view.rect.height {
+            self.screen.cursor_position(&Position {
+                x: view.rect.x,
+                y: view.rect.y + window_row,
+            });
 
             // 如果有绘制内容就绘制(每次移动窗口内容都会重新绘制. 实际上只有offset.y发生变化才会导致现实的内容的重新绘制)
             // 绘制的内容是当前终端位置(窗口位置)加上光标所在文本的位置(显示位置). 比如开始状态下都在1,
             // 那么从上至下正常显示。1-1，2-2，3-3=>窗口第一行显示文本第一行,窗口第二行显示文本第二行,...
             // 当向下移动到第8行时为: 1-8，2-9，3-10=>窗口第一行显示文本第八行,窗口第二行显示文本第九行,...
             // 当向上移动到第7行时为: 1-7，2-8，3-9=>窗口第一行显示文本第七行,窗口第二行显示文本第八行,...
-            if let Some(row) = document.row(terminal_row as usize + self.get_offset().y) {
-                self.draw_document_row(row);
-            } else if terminal_row == height / 2 && self.get_show_welcome() && document.is_empty() {
-                self.draw_welcome_message();
+            let index = window_row + view.offset.y;
+            if let Some(row) = document.row(index) {
+                self.draw_line_number(view, index, gutter_width);
+                self.draw_document_row(view, row, index, text_width, tab_width);
             } else {
-                self.terminal.draw_row("~");
+                let welcome = window_row >= welcome_start
+                    && self.get_show_welcome()
+                    && document.is_empty()
+                    && self.draw_welcome_message(window_row - welcome_start, view.rect.width);
+                if !welcome {
+                    self.screen.draw_text("~");
+                    self.draw_padding(view.rect.width.saturating_sub(1));
+                }
+            }
+        }
+    }
+
+    /// 绘制窗口之间的分隔线
+    fn draw_separators(&self, separators: &[(Split, Rect)]) {
+        self.screen.set_fg_color(self.config.colors.line_number_fg);
+        for (direction, rect) in separators {
+            match direction {
+                Split::Horizontal => {
+                    self.screen.cursor_position(&Position {
+                        x: rect.x,
+                        y: rect.y,
+                    });
+                    self.screen.draw_text(&"─".repeat(rect.width));
+                }
+                Split::Vertical => {
+                    for y in rect.y..rect.y + rect.height {
+                        self.screen.cursor_position(&Position { x: rect.x, y });
+                        self.screen.draw_text("│");
+                    }
+                }
             }
         }
+        self.screen.reset_fg_color();
+    }
+
+    /// 行号栏宽度(行号位数加一个空格), 不显示行号时为0
+    fn gutter_width(&self) -> usize {
+        self.gutter_width_of(&self.document.borrow())
+    }
+
+    /// 指定文档的行号栏宽度
+    fn gutter_width_of(&self, document: &Document) -> usize {
+        if self.get_line_numbers() == LineNumbers::Off {
+            return 0;
+        }
+        let len = document.len().max(1);
+        len.to_string().len() + 1
+    }
+
+    /// 文本区域宽度(当前窗口宽度减去行号栏宽度)
+    fn text_area_width(&self) -> usize {
+        self.viewport().width.saturating_sub(self.gutter_width())
+    }
+
+    /// 绘制行号, index为行在文档中的下标, 相对行号以窗口中的光标为准
+    fn draw_line_number(&self, view: &View, index: usize, gutter_width: usize) {
+        if gutter_width == 0 {
+            return;
+        }
+        let cursor_y = view.cursor_position.y;
+        let number = match self.get_line_numbers() {
+            LineNumbers::Relative => index.abs_diff(cursor_y),
+            LineNumbers::Hybrid if index != cursor_y => index.abs_diff(cursor_y),
+            _ => index + 1,
+        };
+        self.screen.set_fg_color(self.config.colors.line_number_fg);
+        self.screen
+            .draw_text(&format!("{:>width$} ", number, width = gutter_width - 1));
+        self.screen.reset_fg_color();
+    }
+
+    /// 切换行号显示模式
+    fn toggle_line_numbers(&self) {
+        let mode = self.get_line_numbers().next();
+        *self.line_numbers.borrow_mut() = mode;
+        self.rewrite_status_message(&format!("Line numbers: {:?}", mode));
     }
 
     /// 文档上下翻动
@@ -324,10 +2240,12 @@ impl Editor {
     /// 假设光标开始向上移动, 当y = 7时, 对于y的判断条件(if 7 < 1 & else if 7 > 8)均不成立,且此后移动7次光标都不会导致文本显示变化(此处假设height=8)
     /// 假设光标开始向上移动, 当y = 0时, 对于y的判断条件(if 0 < 1 & else if 0 > 8)前者成立,此时窗口中的内容就会改变, 因为offset.y改变(y = offset.y = 0)了. 那么导致draw_rows方法改变.
     /// 那么实际上只有当到最上面一行或最下面一行再进行往上面移动或者往下面移动才会导致offset.y发生变化
+    /// 水平方向使用显示列计算, 保证宽字符和制表符也能完整显示
     pub fn scroll(&self) {
-        let Position { x, y } = self.get_cursor_position();
-        let width = self.terminal.size().width as usize;
-        let height = self.terminal.size().height as usize;
+        let y = self.get_cursor_position().y;
+        let x = self.get_cursor_column();
+        let width = self.text_area_width();
+        let height = self.viewport().height;
         let mut offset = self.offset.borrow_mut();
 
         if y < offset.y {
@@ -343,8 +2261,20 @@ impl Editor {
         }
     }
 
+    /// 使光标所在行显示在屏幕中间, 之后通过scroll调整水平方向的偏移
+    pub fn scroll_to_center(&self) {
+        let y = self.get_cursor_position().y;
+        let height = self.viewport().height;
+        self.offset
+            .borrow_mut()
+            .set_position_y(y.saturating_sub(height / 2));
+        self.scroll();
+    }
+
     /// 移动光标
     pub fn move_cursor(&self, key: Key) {
+        let terminal_height = self.viewport().height;
+        let tab_width = self.settings().tab_width;
         let mut cursor_postion = self.cursor_position.borrow_mut();
         let (mut x, mut y) = (cursor_postion.x, cursor_postion.y);
         let document = self.document.borrow();
@@ -352,22 +2282,19 @@ impl Editor {
         let height = if !document.is_empty() {
             document.len()
         } else {
-            self.terminal.size().height.saturating_sub(1) as usize
+            terminal_height.saturating_sub(1)
         };
         let mut width = if let Some(row) = document.row(y) {
             row.len()
         } else {
             0
-            // self.terminal.size().width.saturating_sub(1) as usize
+            // self.screen.size().width.saturating_sub(1) as usize
         };
-        let terminal_height = self.terminal.size().height as usize;
+        // 上下移动时保持光标所在的显示列不变, 而不是字位下标
+        let column = document.row(y).map_or(0, |row| row.column_of(x, tab_width));
         match key {
             Key::Up => y = y.saturating_sub(1),
-            Key::Down => {
-                if y < height {
-                    y = y.saturating_add(1)
-                }
-            }
+            Key::Down if y < height => y = y.saturating_add(1),
             Key::Left => {
                 if x > 0 {
                     x -= 1;
@@ -388,16 +2315,10 @@ impl Editor {
                     x = 0;
                 }
             }
-            Key::PageUp => {
-                y = if y > terminal_height {
-                    y - terminal_height
-                } else {
-                    0
-                }
-            }
+            Key::PageUp => y = y.saturating_sub(terminal_height),
             Key::PageDown => {
                 y = if y.saturating_add(terminal_height) < height {
-                    y + terminal_height as usize
+                    y + terminal_height
                 } else {
                     height
                 }
@@ -406,6 +2327,11 @@ impl Editor {
             Key::End => x = width,
             _ => (),
         }
+        if matches!(key, Key::Up | Key::Down | Key::PageUp | Key::PageDown) {
+            x = document
+                .row(y)
+                .map_or(0, |row| row.index_of(column, tab_width));
+        }
 
         width = if let Some(row) = document.row(y) {
             row.len()
@@ -419,6 +2345,14 @@ impl Editor {
         cursor_postion.set_position_y(y);
     }
 
+    /// 获取是否正在拖动选择
+    fn get_dragging(&self) -> bool {
+        *self.dragging.borrow()
+    }
+    /// 设置是否正在拖动选择
+    fn set_dragging(&self, value: bool) {
+        *self.dragging.borrow_mut() = value
+    }
     fn get_show_welcome(&self) -> bool {
         *self.show_welcome.borrow()
     }
@@ -437,27 +2371,86 @@ impl Editor {
     fn get_cursor_position(&self) -> Position {
         *self.cursor_position.borrow()
     }
+    /// 获取行号显示模式
+    fn get_line_numbers(&self) -> LineNumbers {
+        *self.line_numbers.borrow()
+    }
+    /// 获取光标所在的显示列
+    fn get_cursor_column(&self) -> usize {
+        let Position { x, y } = self.get_cursor_position();
+        let tab_width = self.settings().tab_width;
+        self.document
+            .borrow()
+            .row(y)
+            .map_or(0, |row| row.column_of(x, tab_width))
+    }
     /// 获取offset(偏移量)
     fn get_offset(&self) -> Position {
         *self.offset.borrow()
     }
+    /// 获取当前缓冲区的下标
+    fn get_current_buffer(&self) -> usize {
+        *self.current_buffer.borrow()
+    }
+    /// 获取当前窗口的下标
+    fn get_current_window(&self) -> usize {
+        *self.current_window.borrow()
+    }
     /// 获取document中的文件名
     fn get_documnet_filename(&self) -> Option<String> {
         self.document.borrow().file_name.clone()
     }
     /// 修改document中的文件名
     fn reset_document_filename(&self, filename: Option<String>) {
-        let mut document = self.document.borrow_mut();
-        document.file_name = filename
+        self.document.borrow_mut().set_file_name(filename)
     }
-    fn save_document(&self) -> Result<(), std::io::Error> {
+    fn save_document(&self) -> Result<(), Error> {
         self.document.borrow_mut().save()
     }
     /// 当前光标处插入字符
+    /// 按Tab键且设置了expand_tabs时插入空格直到下一个制表位
+    fn insert_tab_spaces(&self) {
+        self.delete_selection();
+        let tab_width = self.settings().tab_width;
+        let spaces = tab_width - self.get_cursor_column() % tab_width;
+        let position = self
+            .document
+            .borrow_mut()
+            .insert_text(&self.get_cursor_position(), &" ".repeat(spaces));
+        *self.cursor_position.borrow_mut() = position;
+    }
+    /// 当前文档中指定行的长度, 行不存在时为0
+    fn row_len(&self, y: usize) -> usize {
+        self.document.borrow().row(y).map_or(0, Row::len)
+    }
+    /// 当前文档的设置(与文件类型有关)
+    fn settings(&self) -> FileSettings {
+        self.settings_of(&self.document.borrow())
+    }
+    /// 指定文档的设置(与文件类型有关)
+    fn settings_of(&self, document: &Document) -> FileSettings {
+        self.config.settings_for(document.file_type().name())
+    }
     fn insert_chat_at_document(&self, c: char) {
         let cursor_position = &self.get_cursor_position();
         self.document.borrow_mut().inesrt(cursor_position, c);
     }
+    /// 退格: 有选中内容时删除选中的文本, 否则删除光标前的字符
+    fn backspace(&self) {
+        if self.delete_selection() {
+            return;
+        }
+        self.move_cursor(Key::Left);
+        self.delete_chat_at_document()
+    }
+    /// 删除: 有选中内容时删除选中的文本, 否则删除光标处的字符
+    fn delete_forward(&self) {
+        if self.delete_selection() {
+            return;
+        }
+        let cursor_position = self.get_cursor_position();
+        self.document.borrow_mut().delete(&cursor_position);
+    }
     /// 当前光标处删除字符
     fn delete_chat_at_document(&self) {
         let cursor_position = &self.get_cursor_position();
@@ -469,7 +2462,6 @@ impl Editor {
 
     /// 修改status_message
     fn rewrite_status_message(&self, text: &str) {
-        let mut statue_message = self.status_message.borrow_mut();
-        statue_message.text = String::from(text);
+        *self.status_message.borrow_mut() = StatusMessage::from(String::from(text));
     }
 }
@@ -0,0 +1,274 @@
+use super::error;
+use std::io::{Error, ErrorKind};
+
+/// 检测二进制文件以及无BOM的UTF-16时只检查文件开头的这部分内容
+const SAMPLE_LEN: usize = 8192;
+
+/// 文件编码, 打开时检测, 保存时按原编码写回
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// 带BOM的UTF-8
+    Utf8Bom,
+    /// 不带BOM的UTF-16
+    Utf16Le,
+    Utf16Be,
+    /// 带BOM的UTF-16
+    Utf16LeBom,
+    Utf16BeBom,
+    Gbk,
+    /// ISO-8859-1, 每个字节对应一个字符, 任何内容都可以解码
+    Latin1,
+}
+
+impl Encoding {
+    /// 所有支持的编码, 用于提示
+    pub const ALL: [Encoding; 8] = [
+        Encoding::Utf8,
+        Encoding::Utf8Bom,
+        Encoding::Utf16Le,
+        Encoding::Utf16Be,
+        Encoding::Utf16LeBom,
+        Encoding::Utf16BeBom,
+        Encoding::Gbk,
+        Encoding::Latin1,
+    ];
+
+    /// 显示名称
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8-BOM",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Utf16LeBom => "UTF-16LE-BOM",
+            Encoding::Utf16BeBom => "UTF-16BE-BOM",
+            Encoding::Gbk => "GBK",
+            Encoding::Latin1 => "Latin-1",
+        }
+    }
+
+    /// 根据名称查找编码, 忽略大小写以及`-`和`_`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name: String = name
+            .chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_lowercase();
+        match name.as_str() {
+            "utf8" => Some(Encoding::Utf8),
+            "utf8bom" => Some(Encoding::Utf8Bom),
+            "utf16le" => Some(Encoding::Utf16Le),
+            "utf16be" => Some(Encoding::Utf16Be),
+            "utf16" | "utf16lebom" => Some(Encoding::Utf16LeBom),
+            "utf16bebom" => Some(Encoding::Utf16BeBom),
+            "gbk" | "gb2312" | "cp936" => Some(Encoding::Gbk),
+            "latin1" | "iso88591" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    /// 检测文件内容的编码, 无法作为文本打开(二进制文件)时返回None
+    /// 依次检查BOM, 无BOM的UTF-16, UTF-8, GBK, 最后使用Latin-1
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            return Some(Encoding::Utf8Bom);
+        }
+        if bytes.starts_with(&[0xFF, 0xFE]) {
+            return Some(Encoding::Utf16LeBom);
+        }
+        if bytes.starts_with(&[0xFE, 0xFF]) {
+            return Some(Encoding::Utf16BeBom);
+        }
+
+        let sample = &bytes[..bytes.len().min(SAMPLE_LEN)];
+        if let Some(encoding) = Encoding::detect_utf16(sample) {
+            return Some(encoding);
+        }
+        // 文本文件中几乎不会出现NUL
+        if sample.contains(&0) {
+            return None;
+        }
+        if std::str::from_utf8(bytes).is_ok() {
+            return Some(Encoding::Utf8);
+        }
+        if Encoding::looks_like_gbk(bytes) {
+            return Some(Encoding::Gbk);
+        }
+        Some(Encoding::Latin1)
+    }
+
+    /// GBK: 可以按GBK解码, 并且双字节字符大多在GB2312的汉字区(两个字节都在0xA1到0xFE之间)
+    /// Latin-1文本中重音字母之后通常是ASCII字母(如`\xe9t\xe9`), 也能组成有效的GBK双字节字符, 只检查能否解码会误判
+    fn looks_like_gbk(bytes: &[u8]) -> bool {
+        if encoding_rs::GBK
+            .decode_without_bom_handling_and_without_replacement(bytes)
+            .is_none()
+        {
+            return false;
+        }
+        let is_gb2312 = |byte: u8| (0xA1..=0xFE).contains(&byte);
+        let mut pairs = 0;
+        let mut gb2312 = 0;
+        let mut index = 0;
+        while index < bytes.len() {
+            let lead = bytes[index];
+            // ASCII以及单字节的0x80(欧元符号)
+            if lead <= 0x80 {
+                index += 1;
+                continue;
+            }
+            pairs += 1;
+            if is_gb2312(lead) && bytes.get(index + 1).copied().is_some_and(is_gb2312) {
+                gb2312 += 1;
+            }
+            index += 2;
+        }
+        pairs > 0 && gb2312 * 4 >= pairs * 3
+    }
+
+    /// 无BOM的UTF-16: ASCII字符的高字节为0, 因此奇数位或偶数位上几乎全是NUL
+    fn detect_utf16(sample: &[u8]) -> Option<Self> {
+        if sample.len() < 2 || !sample.len().is_multiple_of(2) {
+            return None;
+        }
+        let pairs = sample.len() / 2;
+        let even = sample.iter().step_by(2).filter(|b| **b == 0).count();
+        let odd = sample
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count();
+        if odd * 10 >= pairs * 9 && even == 0 {
+            Some(Encoding::Utf16Le)
+        } else if even * 10 >= pairs * 9 && odd == 0 {
+            Some(Encoding::Utf16Be)
+        } else {
+            None
+        }
+    }
+
+    /// 按编码解码, 内容不符合编码时返回Decode错误
+    pub fn decode(self, bytes: &[u8]) -> Result<String, error::Error> {
+        let decoded = match self {
+            Encoding::Utf8 => String::from_utf8(bytes.to_vec()).ok(),
+            Encoding::Utf8Bom => {
+                let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+                String::from_utf8(bytes.to_vec()).ok()
+            }
+            Encoding::Utf16Le | Encoding::Utf16LeBom => {
+                let bytes = bytes.strip_prefix(&[0xFF, 0xFE]).unwrap_or(bytes);
+                encoding_rs::UTF_16LE
+                    .decode_without_bom_handling_and_without_replacement(bytes)
+                    .map(|text| text.into_owned())
+            }
+            Encoding::Utf16Be | Encoding::Utf16BeBom => {
+                let bytes = bytes.strip_prefix(&[0xFE, 0xFF]).unwrap_or(bytes);
+                encoding_rs::UTF_16BE
+                    .decode_without_bom_handling_and_without_replacement(bytes)
+                    .map(|text| text.into_owned())
+            }
+            Encoding::Gbk => encoding_rs::GBK
+                .decode_without_bom_handling_and_without_replacement(bytes)
+                .map(|text| text.into_owned()),
+            Encoding::Latin1 => Some(bytes.iter().map(|b| char::from(*b)).collect()),
+        };
+        decoded.ok_or(error::Error::Decode(self.name()))
+    }
+
+    /// 按编码编码, 文本中有该编码无法表示的字符时返回错误
+    /// 打开时有BOM的文件保存时也写入BOM; encoding_rs不支持编码为UTF-16, 因此UTF-16和Latin-1都自行处理
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, Error> {
+        let unmappable = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "text contains characters that cannot be encoded as {}",
+                    self.name()
+                ),
+            )
+        };
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Utf8Bom => {
+                let mut bytes = vec![0xEF, 0xBB, 0xBF];
+                bytes.extend_from_slice(text.as_bytes());
+                Ok(bytes)
+            }
+            Encoding::Utf16Le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            Encoding::Utf16Be => Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            Encoding::Utf16LeBom => Ok([0xFF, 0xFE]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect()),
+            Encoding::Utf16BeBom => Ok([0xFE, 0xFF]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+                .collect()),
+            Encoding::Gbk => {
+                let (bytes, _, had_errors) = encoding_rs::GBK.encode(text);
+                if had_errors {
+                    return Err(unmappable());
+                }
+                Ok(bytes.into_owned())
+            }
+            Encoding::Latin1 => text
+                .chars()
+                .map(|c| u8::try_from(u32::from(c)).map_err(|_| unmappable()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf16_bom_round_trips() {
+        let text = "hello, world\n0123456789\n\u{4e2d}\n";
+        for (encoding, bom) in [
+            (Encoding::Utf16Le, None),
+            (Encoding::Utf16Be, None),
+            (Encoding::Utf16LeBom, Some([0xFF, 0xFE])),
+            (Encoding::Utf16BeBom, Some([0xFE, 0xFF])),
+        ] {
+            let bytes = encoding.encode(text).unwrap();
+            assert_eq!(
+                bytes.len(),
+                text.encode_utf16().count() * 2 + bom.map_or(0, |bom| bom.len())
+            );
+            if let Some(bom) = bom {
+                assert!(bytes.starts_with(&bom));
+            }
+            assert_eq!(Encoding::detect(&bytes), Some(encoding));
+            assert_eq!(encoding.decode(&bytes).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn latin1_is_not_taken_for_gbk() {
+        // 都可以按GBK解码
+        for bytes in [&b"caf\xe9s"[..], b"\xe9t\xe9s", b"na\xefve"] {
+            assert_eq!(Encoding::detect(bytes), Some(Encoding::Latin1));
+        }
+        // "中文编码"
+        let gbk = b"\xd6\xd0\xce\xc4\xb1\xe0\xc2\xeb\n";
+        assert_eq!(Encoding::detect(gbk), Some(Encoding::Gbk));
+        assert_eq!(
+            Encoding::Gbk.decode(gbk).unwrap(),
+            "\u{4e2d}\u{6587}\u{7f16}\u{7801}\n"
+        );
+    }
+
+    #[test]
+    fn decode_errors_are_not_binary_files() {
+        assert!(matches!(
+            Encoding::Utf8.decode(b"\xff"),
+            Err(error::Error::Decode("UTF-8"))
+        ));
+        assert_eq!(Encoding::detect(b"\x00\x01\x02"), None);
+    }
+}
@@ -0,0 +1,43 @@
+use std::{fmt, io};
+
+/// 编辑器的错误类型
+#[derive(Debug)]
+pub enum Error {
+    /// 读写文件失败(错误信息中包含操作及路径)
+    Io(io::Error),
+    /// 文件看起来是二进制文件, 没有打开
+    BinaryFile,
+    /// 文件内容不符合指定的编码(参数为编码名称)
+    Decode(&'static str),
+    /// 终端初始化或输出失败(例如标准输出不是终端)
+    Terminal(io::Error),
+    /// 读取输入失败或输入流已关闭
+    Input(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::BinaryFile => write!(f, "binary file"),
+            Error::Decode(encoding) => write!(f, "file is not valid {}", encoding),
+            Error::Terminal(err) => write!(f, "terminal error: {}", err),
+            Error::Input(err) => write!(f, "input error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) | Error::Terminal(err) | Error::Input(err) => Some(err),
+            Error::BinaryFile | Error::Decode(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,206 @@
+use super::highlighting;
+
+/// 文件类型, 根据文件扩展名确定, 决定了文档的高亮规则
+#[derive(Debug, Clone, Copy)]
+pub struct FileType {
+    name: &'static str,
+    options: HighlightingOptions,
+}
+
+/// 高亮规则
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HighlightingOptions {
+    numbers: bool,
+    quotes: &'static [char],
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    // 以指定前缀开头的行整行使用对应的高亮类型(如Markdown标题, TOML表头)
+    line_markers: &'static [(&'static str, highlighting::Type)],
+    // 分段的文件(如diff), 段内和段外的行使用不同的整行高亮
+    sections: Option<Sections>,
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+}
+
+/// 分段的规则: 以start开头的行开始一段, 段内的行以lines中的前缀开头(或为空行), 遇到其他行时该段结束
+/// 例如diff中 "@@" 开始一个hunk, hunk中的 "--- x" 是删除的行, 只有hunk之外的 "--- " 才是文件头
+#[derive(Debug, Clone, Copy)]
+pub struct Sections {
+    pub start: &'static str,
+    pub lines: &'static [&'static str],
+    /// 只在段外使用的整行高亮前缀, 优先于line_markers
+    pub headers: &'static [(&'static str, highlighting::Type)],
+}
+
+impl Default for FileType {
+    fn default() -> Self {
+        Self {
+            name: "No filetype",
+            options: HighlightingOptions::default(),
+        }
+    }
+}
+
+impl FileType {
+    /// 文件类型名称
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+    /// 高亮规则
+    pub fn highlighting_options(&self) -> &HighlightingOptions {
+        &self.options
+    }
+
+    /// 根据文件名(扩展名)确定文件类型
+    pub fn from(file_name: &str) -> Self {
+        let extension = file_name.rsplit_once('.').map(|(_, extension)| extension);
+        match extension {
+            Some("rs") => Self {
+                name: "Rust",
+                options: HighlightingOptions {
+                    numbers: true,
+                    quotes: &['"'],
+                    line_comment: Some("//"),
+                    block_comment: Some(("/*", "*/")),
+                    line_markers: &[],
+                    sections: None,
+                    keywords: &[
+                        "as", "async", "await", "break", "const", "continue", "crate", "dyn",
+                        "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let",
+                        "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self",
+                        "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+                        "use", "where", "while",
+                    ],
+                    types: &[
+                        "bool", "char", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16",
+                        "u32", "u64", "u128", "usize", "f32", "f64", "str", "String", "Vec",
+                        "Option", "Result", "Box", "Some", "None", "Ok", "Err",
+                    ],
+                },
+            },
+            Some("toml") => Self {
+                name: "TOML",
+                options: HighlightingOptions {
+                    numbers: true,
+                    quotes: &['"', '\''],
+                    line_comment: Some("#"),
+                    block_comment: None,
+                    line_markers: &[("[", highlighting::Type::Primitive)],
+                    sections: None,
+                    keywords: &["true", "false"],
+                    types: &[],
+                },
+            },
+            Some("md") | Some("markdown") => Self {
+                name: "Markdown",
+                options: HighlightingOptions {
+                    numbers: false,
+                    quotes: &['`'],
+                    line_comment: None,
+                    block_comment: Some(("<!--", "-->")),
+                    line_markers: &[
+                        ("#", highlighting::Type::Keyword),
+                        (">", highlighting::Type::Comment),
+                        ("```", highlighting::Type::String),
+                    ],
+                    sections: None,
+                    keywords: &[],
+                    types: &[],
+                },
+            },
+            Some("json") => Self {
+                name: "JSON",
+                options: HighlightingOptions {
+                    numbers: true,
+                    quotes: &['"'],
+                    line_comment: None,
+                    block_comment: None,
+                    line_markers: &[],
+                    sections: None,
+                    keywords: &["true", "false", "null"],
+                    types: &[],
+                },
+            },
+            Some("sh") | Some("bash") | Some("zsh") => Self {
+                name: "Shell",
+                options: HighlightingOptions {
+                    numbers: true,
+                    quotes: &['"', '\''],
+                    line_comment: Some("#"),
+                    block_comment: None,
+                    line_markers: &[],
+                    sections: None,
+                    keywords: &[
+                        "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done",
+                        "case", "esac", "in", "function", "return", "local", "export", "select",
+                    ],
+                    types: &[
+                        "echo", "cd", "exit", "read", "set", "unset", "source", "test", "shift",
+                        "printf", "eval", "exec", "trap",
+                    ],
+                },
+            },
+            Some("diff") | Some("patch") => Self {
+                name: "Diff",
+                options: HighlightingOptions {
+                    numbers: false,
+                    quotes: &[],
+                    line_comment: None,
+                    block_comment: None,
+                    line_markers: &[
+                        ("@@", highlighting::Type::Primitive),
+                        ("+", highlighting::Type::String),
+                        ("-", highlighting::Type::Number),
+                    ],
+                    sections: Some(Sections {
+                        start: "@@",
+                        lines: &[" ", "+", "-", "\\"],
+                        headers: &[
+                            ("+++ ", highlighting::Type::Keyword),
+                            ("--- ", highlighting::Type::Keyword),
+                            ("diff ", highlighting::Type::Keyword),
+                        ],
+                    }),
+                    keywords: &[],
+                    types: &[],
+                },
+            },
+            _ => Self::default(),
+        }
+    }
+}
+
+impl HighlightingOptions {
+    /// 是否高亮数字
+    pub fn numbers(&self) -> bool {
+        self.numbers
+    }
+    /// 字符串的引号
+    pub fn quotes(&self) -> &'static [char] {
+        self.quotes
+    }
+    /// 单行注释前缀
+    pub fn line_comment(&self) -> Option<&'static str> {
+        self.line_comment
+    }
+    /// 多行注释的开始和结束标记
+    pub fn block_comment(&self) -> Option<(&'static str, &'static str)> {
+        self.block_comment
+    }
+    /// 整行高亮的前缀
+    pub fn line_markers(&self) -> &'static [(&'static str, highlighting::Type)] {
+        self.line_markers
+    }
+    /// 分段的规则
+    pub fn sections(&self) -> Option<&Sections> {
+        self.sections.as_ref()
+    }
+    /// 关键字
+    pub fn keywords(&self) -> &'static [&'static str] {
+        self.keywords
+    }
+    /// 类型
+    pub fn types(&self) -> &'static [&'static str] {
+        self.types
+    }
+}
@@ -0,0 +1,182 @@
+use super::{backend::Rgb, utils};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// 文字属性
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Attributes {
+    pub bold: bool,
+    pub underline: bool,
+    /// 交换前景色和背景色
+    pub reverse: bool,
+}
+
+/// 屏幕上的一个单元格
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    /// 单元格中的字位, 宽字符之后的单元格为空字符串
+    pub grapheme: String,
+    pub fg: Option<Rgb>,
+    pub bg: Option<Rgb>,
+    pub attributes: Attributes,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            grapheme: String::from(" "),
+            fg: None,
+            bg: None,
+            attributes: Attributes::default(),
+        }
+    }
+}
+
+impl Cell {
+    /// 是否为空白单元格(清屏后的状态)
+    pub fn is_blank(&self) -> bool {
+        *self == Cell::default()
+    }
+    /// 是否为宽字符右半部分占用的单元格
+    pub fn is_continuation(&self) -> bool {
+        self.grapheme.is_empty()
+    }
+}
+
+/// 一帧画面: 单元格网格以及光标状态
+/// 绘制操作与终端相同(移动光标, 设置颜色, 在光标处输出文本), 但只修改内存中的网格
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    cells: Vec<Cell>,
+    width: usize,
+    height: usize,
+    cursor: (usize, usize),
+    cursor_visible: bool,
+    fg: Option<Rgb>,
+    bg: Option<Rgb>,
+    attributes: Attributes,
+}
+
+impl Frame {
+    /// 创建指定大小(列数, 行数)的空白画面
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            cells: vec![Cell::default(); width * height],
+            width,
+            height,
+            ..Self::default()
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /// 指定位置的单元格
+    pub fn cell(&self, x: usize, y: usize) -> Option<&Cell> {
+        if x >= self.width {
+            return None;
+        }
+        self.cells.get(y * self.width + x)
+    }
+    /// 第y行的文本(去掉行尾的空格)
+    pub fn row_text(&self, y: usize) -> String {
+        let text: String = (0..self.width)
+            .filter_map(|x| self.cell(x, y))
+            .map(|cell| cell.grapheme.as_str())
+            .collect();
+        text.trim_end().to_string()
+    }
+
+    /// 光标位置(列, 行)
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+    /// 移动光标
+    pub fn set_cursor(&mut self, x: usize, y: usize) {
+        self.cursor = (x, y);
+    }
+    /// 光标是否可见
+    pub fn is_cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+    }
+    /// 设置之后输出的文本的前景色(None为终端默认颜色)
+    pub fn set_fg(&mut self, color: Option<Rgb>) {
+        self.fg = color;
+    }
+    /// 设置之后输出的文本的背景色(None为终端默认颜色)
+    pub fn set_bg(&mut self, color: Option<Rgb>) {
+        self.bg = color;
+    }
+
+    /// 设置之后输出的文本的属性
+    pub fn set_attributes(&mut self, attributes: Attributes) {
+        self.attributes = attributes;
+    }
+
+    /// 清空整个画面
+    pub fn clear(&mut self) {
+        self.cells.fill(Cell::default());
+    }
+    /// 清空光标所在的行
+    pub fn clear_line(&mut self) {
+        let y = self.cursor.1;
+        if y < self.height {
+            self.cells[y * self.width..(y + 1) * self.width].fill(Cell::default());
+        }
+    }
+    /// 移动到下一行行首(已经是最后一行时停留在最后一行)
+    pub fn new_line(&mut self) {
+        self.cursor = (0, (self.cursor.1 + 1).min(self.height.saturating_sub(1)));
+    }
+
+    /// 在光标处输出文本并向右移动光标, 超出行尾的部分被丢弃
+    /// 宽字符占用两个单元格, 零宽的字位附加到前一个单元格中
+    pub fn draw_text(&mut self, text: &str) {
+        for grapheme in text.graphemes(true) {
+            let (x, y) = self.cursor;
+            if y >= self.height {
+                return;
+            }
+            let width = utils::grapheme_width(grapheme);
+            if width == 0 {
+                if x > 0 {
+                    self.cells[y * self.width + x - 1]
+                        .grapheme
+                        .push_str(grapheme);
+                }
+                continue;
+            }
+            if x + width > self.width {
+                return;
+            }
+            self.put(x, y, grapheme.to_string());
+            for offset in 1..width {
+                self.put(x + offset, y, String::new());
+            }
+            self.cursor.0 = x + width;
+        }
+    }
+
+    /// 写入一个单元格, 覆盖宽字符的一半时另一半变为空格(与终端的行为一致)
+    fn put(&mut self, x: usize, y: usize, grapheme: String) {
+        let index = y * self.width + x;
+        let continuation = grapheme.is_empty();
+        if !continuation && self.cells[index].is_continuation() && x > 0 {
+            self.cells[index - 1].grapheme = String::from(" ");
+        }
+        if x + 1 < self.width && self.cells[index + 1].is_continuation() {
+            self.cells[index + 1].grapheme = String::from(" ");
+        }
+        self.cells[index] = Cell {
+            grapheme,
+            fg: self.fg,
+            bg: self.bg,
+            attributes: self.attributes,
+        };
+    }
+}
@@ -0,0 +1,166 @@
+pub use super::frame::Cell;
+use super::{
+    backend::{Attributes, Event, InputSource, Key, RenderTarget, Rgb, Size},
+    editor::Position,
+    error::Error,
+    frame::Frame,
+};
+use std::{cell::RefCell, collections::VecDeque, io, rc::Rc};
+
+/// 按顺序提供预先给定的事件的输入源, 用于测试或在其他程序中驱动编辑器
+/// 事件用完后返回错误(编辑器随之结束运行), 因此脚本通常以退出命令(Ctrl-Q)结尾
+#[derive(Debug, Default)]
+pub struct ScriptedInput {
+    events: RefCell<VecDeque<Event>>,
+}
+
+impl ScriptedInput {
+    pub fn new(events: Vec<Event>) -> Self {
+        Self {
+            events: RefCell::new(events.into()),
+        }
+    }
+    /// 由按键序列构建
+    pub fn from_keys(keys: Vec<Key>) -> Self {
+        Self::new(keys.into_iter().map(Event::Key).collect())
+    }
+    /// 在末尾追加事件
+    pub fn push(&self, event: Event) {
+        self.events.borrow_mut().push_back(event);
+    }
+    /// 在末尾追加输入文本(每个字符一个按键)
+    pub fn push_text(&self, text: &str) {
+        for c in text.chars() {
+            self.push(Event::Key(Key::Char(c)));
+        }
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn read_event(&self) -> Result<Event, Error> {
+        self.events.borrow_mut().pop_front().ok_or_else(|| {
+            Error::Input(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "end of scripted input",
+            ))
+        })
+    }
+}
+
+/// 内存中的屏幕状态
+#[derive(Debug, Default)]
+struct Grid {
+    frame: Frame,
+    frames: usize, // 已经输出的帧数
+}
+
+/// 绘制到内存中字符网格的输出目标, 用于测试或在其他程序中嵌入编辑器
+/// 可以克隆, 克隆出的实例共享同一个网格, 把其中一个交给编辑器后通过另一个查看画面
+#[derive(Debug, Clone)]
+pub struct MemoryScreen {
+    grid: Rc<RefCell<Grid>>,
+}
+
+impl MemoryScreen {
+    /// 创建指定大小(列数, 行数)的空白屏幕
+    pub fn new(width: u16, height: u16) -> Self {
+        let screen = Self {
+            grid: Rc::new(RefCell::new(Grid::default())),
+        };
+        screen.resize(width, height);
+        screen
+    }
+
+    /// 修改屏幕大小, 画面被清空, 编辑器收到Resize事件后按新的大小重绘
+    pub fn resize(&self, width: u16, height: u16) {
+        self.grid.borrow_mut().frame = Frame::new(width as usize, height as usize);
+    }
+
+    /// 指定位置的单元格
+    pub fn cell(&self, x: usize, y: usize) -> Option<Cell> {
+        self.grid.borrow().frame.cell(x, y).cloned()
+    }
+    /// 第y行的文本(去掉行尾的空格)
+    pub fn row_text(&self, y: usize) -> String {
+        self.grid.borrow().frame.row_text(y)
+    }
+    /// 整个屏幕的文本, 每行以换行符分隔
+    pub fn text(&self) -> String {
+        let grid = self.grid.borrow();
+        (0..grid.frame.height())
+            .map(|y| grid.frame.row_text(y))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+    /// 光标位置(列, 行)
+    pub fn cursor(&self) -> (usize, usize) {
+        self.grid.borrow().frame.cursor()
+    }
+    /// 光标是否可见
+    pub fn is_cursor_visible(&self) -> bool {
+        self.grid.borrow().frame.is_cursor_visible()
+    }
+    /// 已经输出的帧数
+    pub fn frames(&self) -> usize {
+        self.grid.borrow().frames
+    }
+}
+
+impl RenderTarget for MemoryScreen {
+    /// 与终端相同, 留出状态栏, 消息栏以及最后一行
+    fn size(&self) -> Size {
+        let frame = &self.grid.borrow().frame;
+        Size {
+            width: frame.width() as u16,
+            height: frame.height().saturating_sub(3) as u16,
+        }
+    }
+    fn update_size(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn cursor_position(&self, position: &Position) {
+        self.grid
+            .borrow_mut()
+            .frame
+            .set_cursor(position.x(), position.y());
+    }
+    fn clear_screen(&self) {
+        self.grid.borrow_mut().frame.clear();
+    }
+    fn clear_current_line(&self) {
+        self.grid.borrow_mut().frame.clear_line();
+    }
+    fn draw_text(&self, text: &str) {
+        self.grid.borrow_mut().frame.draw_text(text);
+    }
+    fn draw_row(&self, text: &str) {
+        let frame = &mut self.grid.borrow_mut().frame;
+        frame.draw_text(text);
+        frame.new_line();
+    }
+    fn cursor_hide(&self) {
+        self.grid.borrow_mut().frame.set_cursor_visible(false);
+    }
+    fn cursor_show(&self) {
+        self.grid.borrow_mut().frame.set_cursor_visible(true);
+    }
+    fn flush(&self) -> Result<(), Error> {
+        self.grid.borrow_mut().frames += 1;
+        Ok(())
+    }
+    fn set_bg_color(&self, color: Rgb) {
+        self.grid.borrow_mut().frame.set_bg(Some(color));
+    }
+    fn reset_bg_color(&self) {
+        self.grid.borrow_mut().frame.set_bg(None);
+    }
+    fn set_fg_color(&self, color: Rgb) {
+        self.grid.borrow_mut().frame.set_fg(Some(color));
+    }
+    fn reset_fg_color(&self) {
+        self.grid.borrow_mut().frame.set_fg(None);
+    }
+    fn set_attributes(&self, attributes: Attributes) {
+        self.grid.borrow_mut().frame.set_attributes(attributes);
+    }
+}
@@ -0,0 +1,28 @@
+use termion::color;
+
+/// 高亮类型
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    #[default]
+    None,
+    Number,
+    String,
+    Comment,
+    Keyword,
+    /// 类型名(基本类型以及常用的标准库类型)
+    Primitive,
+}
+
+impl Type {
+    /// 高亮类型对应的前景色, 没有高亮时返回None(使用终端默认颜色)
+    pub fn to_color(self) -> Option<color::Rgb> {
+        match self {
+            Type::None => None,
+            Type::Number => Some(color::Rgb(220, 163, 163)),
+            Type::String => Some(color::Rgb(152, 195, 121)),
+            Type::Comment => Some(color::Rgb(128, 128, 128)),
+            Type::Keyword => Some(color::Rgb(198, 120, 221)),
+            Type::Primitive => Some(color::Rgb(229, 192, 123)),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use super::editor::Position;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// 编辑操作, 每个操作都记录发生的位置, 用于撤销/重做时恢复光标
+#[derive(Debug, Clone)]
+pub enum Operation {
+    /// 在at处插入文本(不包含换行)
+    Insert { at: Position, text: String },
+    /// 删除at处的文本(不包含换行)
+    Delete { at: Position, text: String },
+    /// 在at处将一行分割为两行
+    Split { at: Position },
+    /// 将at.y的下一行追加到at.y行末尾, at.x为合并前at.y行的长度
+    Join { at: Position },
+    /// 在空文档中创建第一行(空行)
+    CreateRow,
+    /// 删除文档中唯一的空行, 使文档重新变为空
+    RemoveRow,
+}
+
+impl Operation {
+    /// 逆操作
+    pub fn inverse(&self) -> Self {
+        match self {
+            Self::Insert { at, text } => Self::Delete {
+                at: *at,
+                text: text.clone(),
+            },
+            Self::Delete { at, text } => Self::Insert {
+                at: *at,
+                text: text.clone(),
+            },
+            Self::Split { at } => Self::Join { at: *at },
+            Self::Join { at } => Self::Split { at: *at },
+            Self::CreateRow => Self::RemoveRow,
+            Self::RemoveRow => Self::CreateRow,
+        }
+    }
+
+    /// 尝试与后一个操作合并(连续输入或连续退格), 合并成功返回true
+    /// 输入空白字符时会开始新的一组, 这样撤销时以单词为单位
+    fn merge(&mut self, next: &Self) -> bool {
+        match (self, next) {
+            (
+                Self::Insert { at, text },
+                Self::Insert {
+                    at: next_at,
+                    text: next_text,
+                },
+            ) => {
+                let adjacent =
+                    at.y == next_at.y && at.x + text.graphemes(true).count() == next_at.x;
+                let word_boundary = next_text.starts_with(char::is_whitespace)
+                    && !text.ends_with(char::is_whitespace);
+                if adjacent && !word_boundary {
+                    text.push_str(next_text);
+                    return true;
+                }
+                false
+            }
+            (
+                Self::Delete { at, text },
+                Self::Delete {
+                    at: next_at,
+                    text: next_text,
+                },
+            ) => {
+                // 连续退格: 后一个删除位置紧挨在前一个删除位置之前
+                if at.y == next_at.y && next_at.x + next_text.graphemes(true).count() == at.x {
+                    *at = *next_at;
+                    text.insert_str(0, next_text);
+                    return true;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 编辑历史
+/// undo/redo中的每一项是一个撤销步骤, 一个步骤可以包含多个操作
+#[derive(Debug)]
+pub struct History {
+    undo: Vec<Vec<Operation>>,
+    redo: Vec<Vec<Operation>>,
+    // 保存时undo的长度, 为None时表示保存时的状态已经无法通过撤销/重做回到
+    saved: Option<usize>,
+    // 为true时下一个操作不会与上一个步骤合并
+    sealed: bool,
+    // 操作组的嵌套层数, 大于0时所有操作都记录到同一个步骤中
+    group: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            saved: Some(0),
+            sealed: true,
+            group: 0,
+        }
+    }
+}
+
+impl History {
+    /// 记录操作
+    pub fn record(&mut self, operation: Operation) {
+        self.clear_redo();
+        if let Some(step) = self.undo.last_mut() {
+            if self.group > 0 {
+                step.push(operation);
+                return;
+            }
+            if !self.sealed && step.len() == 1 && step[0].merge(&operation) {
+                return;
+            }
+        }
+        self.undo.push(vec![operation]);
+        self.sealed = false;
+    }
+
+    /// 结束当前步骤, 之后的操作会记录到新的步骤中
+    pub fn seal(&mut self) {
+        if self.group == 0 {
+            self.sealed = true;
+        }
+    }
+
+    /// 开始一个操作组, 直到end_group之前的所有操作作为一个撤销步骤
+    pub fn begin_group(&mut self) {
+        if self.group == 0 {
+            self.clear_redo();
+            self.undo.push(Vec::new());
+            self.sealed = false;
+        }
+        self.group += 1;
+    }
+
+    /// 结束操作组
+    pub fn end_group(&mut self) {
+        self.group = self.group.saturating_sub(1);
+        if self.group == 0 {
+            // 空的操作组不需要记录
+            if self.undo.last().is_some_and(Vec::is_empty) {
+                self.undo.pop();
+            }
+            self.sealed = true;
+        }
+    }
+
+    /// 取出需要撤销的步骤
+    /// 操作组还没有结束时(如在插入模式中撤销)先结束操作组, 之后的操作记录到新的步骤中
+    pub fn undo(&mut self) -> Option<Vec<Operation>> {
+        self.close_group();
+        let step = self.undo.pop()?;
+        self.redo.push(step.clone());
+        self.sealed = true;
+        Some(step)
+    }
+
+    /// 取出需要重做的步骤, 与撤销相同, 先结束还没有结束的操作组
+    pub fn redo(&mut self) -> Option<Vec<Operation>> {
+        self.close_group();
+        let step = self.redo.pop()?;
+        self.undo.push(step.clone());
+        self.sealed = true;
+        Some(step)
+    }
+
+    /// 标记当前状态为已保存
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.undo.len());
+        self.sealed = true;
+    }
+
+    /// 发生了不在历史记录中的修改(如修改换行符), 撤销/重做都无法再回到保存时的状态
+    pub fn mark_unsaved(&mut self) {
+        self.saved = None;
+    }
+
+    /// 当前状态是否与保存时一致
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.undo.len())
+    }
+
+    /// 结束所有嵌套的操作组, 之后的end_group不再有效果
+    fn close_group(&mut self) {
+        if self.group > 0 {
+            self.group = 1;
+            self.end_group();
+        }
+    }
+
+    /// 新的操作会使重做记录失效
+    fn clear_redo(&mut self) {
+        if self.redo.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        if self.saved.is_some_and(|saved| saved > self.undo.len()) {
+            self.saved = None;
+        }
+    }
+}
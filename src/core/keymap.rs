@@ -0,0 +1,376 @@
+use super::backend::Key;
+use std::collections::HashMap;
+
+/// 可以绑定到按键的命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Command {
+    Quit,
+    Save,
+    Find,
+    Replace,
+    Undo,
+    Redo,
+    Copy,
+    Cut,
+    Paste,
+    ToggleLineNumbers,
+    GotoLine,
+    ToggleLineEnding,
+    ChangeEncoding,
+    OpenFile,
+    ListBuffers,
+    NextBuffer,
+    PreviousBuffer,
+    SplitHorizontal,
+    SplitVertical,
+    CloseWindow,
+    NextWindow,
+    FocusLeft,
+    FocusDown,
+    FocusUp,
+    FocusRight,
+    Suspend,
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    LineStart,
+    LineEnd,
+    Backspace,
+    Delete,
+    ClearSelection,
+}
+
+/// 命令注册表: 命令在配置文件中使用的名称
+const COMMANDS: &[(&str, Command)] = &[
+    ("quit", Command::Quit),
+    ("save", Command::Save),
+    ("find", Command::Find),
+    ("replace", Command::Replace),
+    ("undo", Command::Undo),
+    ("redo", Command::Redo),
+    ("copy", Command::Copy),
+    ("cut", Command::Cut),
+    ("paste", Command::Paste),
+    ("toggle_line_numbers", Command::ToggleLineNumbers),
+    ("goto_line", Command::GotoLine),
+    ("toggle_line_ending", Command::ToggleLineEnding),
+    ("change_encoding", Command::ChangeEncoding),
+    ("open_file", Command::OpenFile),
+    ("list_buffers", Command::ListBuffers),
+    ("next_buffer", Command::NextBuffer),
+    ("previous_buffer", Command::PreviousBuffer),
+    ("split_horizontal", Command::SplitHorizontal),
+    ("split_vertical", Command::SplitVertical),
+    ("close_window", Command::CloseWindow),
+    ("next_window", Command::NextWindow),
+    ("focus_left", Command::FocusLeft),
+    ("focus_down", Command::FocusDown),
+    ("focus_up", Command::FocusUp),
+    ("focus_right", Command::FocusRight),
+    ("suspend", Command::Suspend),
+    ("up", Command::Up),
+    ("down", Command::Down),
+    ("left", Command::Left),
+    ("right", Command::Right),
+    ("page_up", Command::PageUp),
+    ("page_down", Command::PageDown),
+    ("line_start", Command::LineStart),
+    ("line_end", Command::LineEnd),
+    ("backspace", Command::Backspace),
+    ("delete", Command::Delete),
+    ("clear_selection", Command::ClearSelection),
+];
+
+impl Command {
+    /// 根据名称查找命令
+    pub fn from_name(name: &str) -> Option<Self> {
+        COMMANDS
+            .iter()
+            .find(|(command_name, _)| *command_name == name)
+            .map(|(_, command)| *command)
+    }
+}
+
+/// 预设的按键绑定
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    #[default]
+    Default,
+    /// 类似Emacs的按键(Ctrl-X前缀)
+    Emacs,
+    /// 类似nano的按键
+    Nano,
+}
+
+impl Preset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Preset::Default),
+            "emacs" => Some(Preset::Emacs),
+            "nano" => Some(Preset::Nano),
+            _ => None,
+        }
+    }
+
+    /// 预设的按键绑定, 在默认绑定的基础上修改
+    fn bindings(self) -> &'static [(&'static str, Command)] {
+        match self {
+            Preset::Default => &[],
+            Preset::Emacs => &[
+                ("Ctrl-x Ctrl-s", Command::Save),
+                ("Ctrl-x Ctrl-c", Command::Quit),
+                ("Ctrl-x Ctrl-f", Command::OpenFile),
+                ("Ctrl-x b", Command::ListBuffers),
+                ("Ctrl-x Right", Command::NextBuffer),
+                ("Ctrl-x Left", Command::PreviousBuffer),
+                ("Ctrl-x 2", Command::SplitHorizontal),
+                ("Ctrl-x 3", Command::SplitVertical),
+                ("Ctrl-x 0", Command::CloseWindow),
+                ("Ctrl-x o", Command::NextWindow),
+                ("Ctrl-x u", Command::Undo),
+                ("Ctrl-x r", Command::Redo),
+                ("Ctrl-s", Command::Find),
+                ("Alt-%", Command::Replace),
+                ("Alt-g g", Command::GotoLine),
+                ("Ctrl-w", Command::Cut),
+                ("Alt-w", Command::Copy),
+                ("Ctrl-y", Command::Paste),
+                ("Ctrl-f", Command::Right),
+                ("Ctrl-b", Command::Left),
+                ("Ctrl-n", Command::Down),
+                ("Ctrl-p", Command::Up),
+                ("Ctrl-a", Command::LineStart),
+                ("Ctrl-e", Command::LineEnd),
+                ("Ctrl-v", Command::PageDown),
+                ("Alt-v", Command::PageUp),
+                ("Ctrl-d", Command::Delete),
+                ("Ctrl-g", Command::ClearSelection),
+                ("Ctrl-z", Command::Suspend),
+            ],
+            Preset::Nano => &[
+                ("Ctrl-o", Command::Save),
+                ("Ctrl-x", Command::Quit),
+                ("Ctrl-w", Command::Find),
+                ("Alt-r", Command::Replace),
+                ("Alt-g", Command::GotoLine),
+                ("Alt-u", Command::Undo),
+                ("Alt-e", Command::Redo),
+                ("Alt-6", Command::Copy),
+                ("Ctrl-k", Command::Cut),
+                ("Ctrl-u", Command::Paste),
+                ("Ctrl-r", Command::OpenFile),
+                ("Alt-.", Command::NextBuffer),
+                ("Alt-,", Command::PreviousBuffer),
+                ("Ctrl-a", Command::LineStart),
+                ("Ctrl-e", Command::LineEnd),
+                ("Ctrl-y", Command::PageUp),
+                ("Ctrl-v", Command::PageDown),
+                ("Ctrl-d", Command::Delete),
+                ("Alt-#", Command::ToggleLineNumbers),
+            ],
+        }
+    }
+}
+
+/// 默认的按键绑定
+const DEFAULT_BINDINGS: &[(&str, Command)] = &[
+    ("Ctrl-q", Command::Quit),
+    ("Ctrl-s", Command::Save),
+    ("Ctrl-f", Command::Find),
+    ("Ctrl-r", Command::Replace),
+    ("Ctrl-z", Command::Undo),
+    ("Ctrl-y", Command::Redo),
+    ("Ctrl-c", Command::Copy),
+    ("Ctrl-x", Command::Cut),
+    ("Ctrl-v", Command::Paste),
+    ("Ctrl-n", Command::ToggleLineNumbers),
+    ("Ctrl-g", Command::GotoLine),
+    ("Ctrl-e", Command::ToggleLineEnding),
+    ("Ctrl-t", Command::ChangeEncoding),
+    ("Ctrl-o", Command::OpenFile),
+    ("Ctrl-b", Command::ListBuffers),
+    ("Alt-n", Command::NextBuffer),
+    ("Alt-p", Command::PreviousBuffer),
+    ("Alt-s", Command::SplitHorizontal),
+    ("Alt-v", Command::SplitVertical),
+    ("Alt-c", Command::CloseWindow),
+    ("Alt-z", Command::Suspend),
+    ("Alt-w", Command::NextWindow),
+    ("Alt-h", Command::FocusLeft),
+    ("Alt-j", Command::FocusDown),
+    ("Alt-k", Command::FocusUp),
+    ("Alt-l", Command::FocusRight),
+    ("Up", Command::Up),
+    ("Down", Command::Down),
+    ("Left", Command::Left),
+    ("Right", Command::Right),
+    ("PageUp", Command::PageUp),
+    ("PageDown", Command::PageDown),
+    ("Home", Command::LineStart),
+    ("End", Command::LineEnd),
+    ("Backspace", Command::Backspace),
+    ("Delete", Command::Delete),
+    ("Esc", Command::ClearSelection),
+];
+
+/// 按键序列查找的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    /// 按键序列绑定的命令
+    Command(Command),
+    /// 按键序列是某个绑定的前缀, 需要继续输入
+    Prefix,
+    /// 没有绑定
+    Unbound,
+}
+
+/// 按键绑定: 按键序列(单个按键或者多个按键组成的组合键, 如Ctrl-K Ctrl-S)到命令的映射
+/// 任何一个绑定都不是另一个绑定的前缀, 因此输入的按键序列要么对应一个命令, 要么需要继续输入, 要么没有绑定
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Vec<Key>, Command>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::preset(Preset::Default)
+    }
+}
+
+impl Keymap {
+    /// 预设的按键绑定
+    pub fn preset(preset: Preset) -> Self {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+        };
+        for (keys, command) in DEFAULT_BINDINGS.iter().chain(preset.bindings()) {
+            if let Ok(keys) = parse_keys(keys) {
+                keymap.bind(keys, Some(*command));
+            }
+        }
+        keymap
+    }
+
+    /// 绑定按键序列, command为None时解除绑定
+    /// 与新绑定互为前缀的已有绑定被移除(例如绑定Ctrl-X Ctrl-S后, Ctrl-X不再单独对应命令)
+    pub fn bind(&mut self, keys: Vec<Key>, command: Option<Command>) {
+        self.bindings.retain(|bound, _| {
+            let len = bound.len().min(keys.len());
+            bound[..len] != keys[..len]
+        });
+        if let Some(command) = command {
+            self.bindings.insert(keys, command);
+        }
+    }
+
+    /// 查找按键序列
+    pub fn lookup(&self, keys: &[Key]) -> Binding {
+        if let Some(command) = self.bindings.get(keys) {
+            return Binding::Command(*command);
+        }
+        if self
+            .bindings
+            .keys()
+            .any(|bound| bound.len() > keys.len() && bound.starts_with(keys))
+        {
+            Binding::Prefix
+        } else {
+            Binding::Unbound
+        }
+    }
+
+    /// 命令绑定的按键序列(用于帮助信息), 有多个时使用最短的一个
+    pub fn keys_for(&self, command: Command) -> Option<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| **bound == command)
+            .map(|(keys, _)| format_keys(keys))
+            .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+    }
+}
+
+/// 解析按键序列, 按键之间以空格分隔, 例如 "Ctrl-k Ctrl-s", "Alt-g g", "PageDown"
+pub fn parse_keys(text: &str) -> Result<Vec<Key>, String> {
+    let keys = text
+        .split_whitespace()
+        .map(parse_key)
+        .collect::<Result<Vec<Key>, String>>()?;
+    if keys.is_empty() {
+        return Err("empty key sequence".to_string());
+    }
+    Ok(keys)
+}
+
+/// 解析单个按键
+/// Ctrl后的字母不区分大小写(终端无法区分), Alt后的字符区分大小写(Alt-S为Alt+Shift+s)
+fn parse_key(text: &str) -> Result<Key, String> {
+    let error = || format!("unknown key '{}'", text);
+    let single = |text: &str| {
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+    if let Some(rest) = text.strip_prefix("Ctrl-") {
+        return single(rest)
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| Key::Ctrl(c.to_ascii_lowercase()))
+            .ok_or_else(error);
+    }
+    if let Some(rest) = text.strip_prefix("Alt-") {
+        return single(rest).map(Key::Alt).ok_or_else(error);
+    }
+    let key = match text {
+        "Up" => Key::Up,
+        "Down" => Key::Down,
+        "Left" => Key::Left,
+        "Right" => Key::Right,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "Backspace" => Key::Backspace,
+        "Delete" => Key::Delete,
+        "Insert" => Key::Insert,
+        "Esc" => Key::Esc,
+        "Tab" => Key::Char('\t'),
+        "Enter" => Key::Char('\n'),
+        "Space" => Key::Char(' '),
+        _ => {
+            if let Some(number) = text.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+                Key::F(number)
+            } else {
+                Key::Char(single(text).ok_or_else(error)?)
+            }
+        }
+    };
+    Ok(key)
+}
+
+/// 按键序列的显示形式(与配置文件中的写法相同)
+pub fn format_keys(keys: &[Key]) -> String {
+    keys.iter()
+        .map(format_key)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn format_key(key: &Key) -> String {
+    match key {
+        Key::Ctrl(c) => format!("Ctrl-{}", c.to_ascii_uppercase()),
+        Key::Alt(c) => format!("Alt-{}", c),
+        Key::Char('\t') => "Tab".to_string(),
+        Key::Char('\n') => "Enter".to_string(),
+        Key::Char(' ') => "Space".to_string(),
+        Key::Char(c) => c.to_string(),
+        Key::F(number) => format!("F{}", number),
+        Key::PageUp => "PageUp".to_string(),
+        Key::PageDown => "PageDown".to_string(),
+        Key::BackTab => "BackTab".to_string(),
+        key => format!("{:?}", key),
+    }
+}
@@ -1,7 +1,24 @@
+pub mod backend;
+mod buffer;
+pub mod config;
 mod constants;
+mod diff;
 mod document;
 pub mod editor;
+mod encoding;
+pub mod error;
+mod filetype;
+mod frame;
+pub mod headless;
+mod highlighting;
+mod history;
+pub mod keymap;
 mod processor;
+mod rope;
 mod row;
+mod rows;
+mod swap;
 mod terminal;
 mod utils;
+mod vim;
+mod window;
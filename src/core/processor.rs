@@ -1,33 +1,244 @@
+use super::{
+    backend::{Event, InputSource},
+    editor::Position,
+    error::Error,
+    keymap::{self, Binding, Command, Keymap},
+};
 ///external crate
-use std::{env, io};
-use termion::{event::Key, input::TermRead};
+use signal_hook::{
+    consts::{SIGTSTP, SIGWINCH},
+    iterator::Signals,
+};
+use std::{
+    cell::RefCell,
+    env, io, mem,
+    path::Path,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+use termion::{
+    event::{self, Key},
+    input::TermRead,
+};
+
+/// 没有事件时产生Tick的间隔
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
 
 /// 处理器类型
-#[derive(Default)]
-pub struct Processor;
+/// 按键和信号分别在单独的线程中读取, 通过channel汇总到一起, 这样窗口大小变化时不需要等待按键就能处理
+pub struct Processor {
+    receiver: Receiver<Result<Event, io::Error>>,
+}
+
+impl Default for Processor {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Processor::spawn_input_reader(sender.clone());
+        Processor::spawn_signal_listener(sender);
+        Self { receiver }
+    }
+}
+
+impl InputSource for Processor {
+    /// 读取输入事件(阻塞直到有事件发生, 超过TICK_INTERVAL没有事件时返回Tick)
+    fn read_event(&self) -> Result<Event, Error> {
+        match self.receiver.recv_timeout(TICK_INTERVAL) {
+            Ok(event) => event.map_err(Error::Input),
+            Err(RecvTimeoutError::Timeout) => Ok(Event::Tick),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::Input(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "input closed",
+            ))),
+        }
+    }
+}
 
 impl Processor {
-    /// 处理按键:函数接受闭包, 该闭包用于处理按键
-    pub fn process_keypress<F>(&self, call: F) -> Result<(), std::io::Error>
-    where
-        F: FnOnce(Key) -> (),
-    {
-        let pressed_key = Processor::read_key()?;
-        Ok(call(pressed_key))
-    }
-
-    /// 读取按键
-    pub fn read_key() -> Result<Key, io::Error> {
-        loop {
-            if let Some(key) = io::stdin().lock().keys().next() {
-                return key;
+    /// 在单独的线程中读取标准输入
+    /// termion无法识别带修饰键的方向键, 这些按键会以Unsupported的形式返回, 需要自行解析
+    fn spawn_input_reader(sender: Sender<Result<Event, io::Error>>) {
+        thread::spawn(move || {
+            for event in io::stdin().events() {
+                let event = match event {
+                    Ok(event::Event::Key(key)) => Ok(Event::Key(key)),
+                    Ok(event::Event::Mouse(mouse)) => Ok(Event::Mouse(mouse)),
+                    Ok(event::Event::Unsupported(bytes)) => {
+                        match Processor::parse_shift_key(&bytes) {
+                            Some(key) => Ok(Event::Shift(key)),
+                            None => continue,
+                        }
+                    }
+                    Err(err) => Err(err),
+                };
+                if sender.send(event).is_err() {
+                    break;
+                }
             }
+        });
+    }
+
+    /// 在单独的线程中监听窗口大小变化以及挂起的信号
+    /// 挂起信号由编辑器处理(先恢复终端再停止进程), 否则停止后终端仍处于raw模式
+    fn spawn_signal_listener(sender: Sender<Result<Event, io::Error>>) {
+        // 无法注册信号时只是不再响应窗口大小变化和挂起, 不影响编辑
+        let Ok(mut signals) = Signals::new([SIGWINCH, SIGTSTP]) else {
+            return;
+        };
+        thread::spawn(move || {
+            for signal in signals.forever() {
+                let event = match signal {
+                    SIGTSTP => Event::Suspend,
+                    _ => Event::Resize,
+                };
+                if sender.send(Ok(event)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// 解析Shift+方向键(ESC [ 1 ; 2 A)
+    fn parse_shift_key(bytes: &[u8]) -> Option<Key> {
+        match bytes {
+            b"\x1b[1;2A" => Some(Key::Up),
+            b"\x1b[1;2B" => Some(Key::Down),
+            b"\x1b[1;2C" => Some(Key::Right),
+            b"\x1b[1;2D" => Some(Key::Left),
+            b"\x1b[1;2H" => Some(Key::Home),
+            b"\x1b[1;2F" => Some(Key::End),
+            _ => None,
+        }
+    }
+
+    /// 读取命令行参数, 可以指定多个文件
+    /// 支持 `lecto path`, `lecto path:line[:col]` 以及 `lecto +line path` 三种形式, 行号和列号从1开始
+    pub fn read_files_for_command() -> Vec<FileArgument> {
+        let mut files = Vec::new();
+        let mut line = None;
+        for argument in env::args().skip(1) {
+            if let Some(number) = argument.strip_prefix('+') {
+                if let Ok(number) = number.parse::<usize>() {
+                    // 作用于下一个文件
+                    line = Some(number);
+                    continue;
+                }
+            }
+            let mut file = FileArgument::parse(&argument);
+            if let Some(line) = line.take() {
+                file.position = Some(Position {
+                    x: 0,
+                    y: line.saturating_sub(1),
+                });
+            }
+            files.push(file);
         }
+        files
     }
+}
 
-    /// 读取命令行参数
-    pub fn read_filename_for_command() -> Option<String> {
-        let args: Vec<String> = env::args().collect();
-        Some(String::from(args.get(1)?))
+/// 按键处理的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// 执行命令
+    Command(Command),
+    /// 输入字符(没有绑定的普通字符)
+    Insert(char),
+    /// 组合键还没有输入完整, 参数为已经输入的部分
+    Pending(String),
+    /// 按键序列没有绑定任何命令
+    Unbound(String),
+}
+
+/// 按键处理器: 根据按键绑定把按键转换为命令
+/// 组合键(如Ctrl-K Ctrl-S)的前缀保存在pending中, 直到输入完整或者不匹配任何绑定为止
+#[derive(Debug, Default)]
+pub struct KeyProcessor {
+    keymap: Keymap,
+    pending: RefCell<Vec<Key>>, // 已经输入的组合键前缀
+}
+
+impl KeyProcessor {
+    pub fn new(keymap: Keymap) -> Self {
+        Self {
+            keymap,
+            pending: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// 处理一个按键
+    pub fn process(&self, key: Key) -> Action {
+        let mut pending = self.pending.borrow_mut();
+        pending.push(key);
+        match self.keymap.lookup(&pending) {
+            Binding::Command(command) => {
+                pending.clear();
+                Action::Command(command)
+            }
+            Binding::Prefix => Action::Pending(keymap::format_keys(&pending)),
+            Binding::Unbound => {
+                let keys = mem::take(&mut *pending);
+                match keys.as_slice() {
+                    [Key::Char(c)] => Action::Insert(*c),
+                    _ => Action::Unbound(keymap::format_keys(&keys)),
+                }
+            }
+        }
+    }
+
+    /// 是否正在输入组合键
+    pub fn is_pending(&self) -> bool {
+        !self.pending.borrow().is_empty()
+    }
+
+    /// 命令绑定的按键(用于帮助信息)
+    pub fn keys_for(&self, command: Command) -> Option<String> {
+        self.keymap.keys_for(command)
+    }
+}
+
+/// 命令行中指定的文件, 以及打开后光标需要跳转的位置
+pub struct FileArgument {
+    pub filename: String,
+    pub position: Option<Position>,
+}
+
+impl FileArgument {
+    /// 解析 `path:line[:col]`, 文件名本身存在时(如文件名中包含冒号)不做解析
+    fn parse(argument: &str) -> Self {
+        let plain = Self {
+            filename: argument.to_string(),
+            position: None,
+        };
+        if Path::new(argument).exists() {
+            return plain;
+        }
+        let mut parts = argument.rsplitn(3, ':');
+        let numbers: Vec<&str> = parts.by_ref().take(2).collect();
+        let parsed = match (numbers.as_slice(), parts.next()) {
+            // path:line:col
+            ([col, line], Some(path)) => match (line.parse::<usize>(), col.parse::<usize>()) {
+                (Ok(line), Ok(col)) => Some((path.to_string(), line, col)),
+                // 只有最后一段是数字时视为 path:line (path中包含冒号)
+                (Err(_), Ok(line)) => Some((format!("{}:{}", path, numbers[1]), line, 1)),
+                _ => None,
+            },
+            // path:line
+            ([line, path], None) => line
+                .parse::<usize>()
+                .ok()
+                .map(|line| (path.to_string(), line, 1)),
+            _ => None,
+        };
+        match parsed {
+            Some((filename, line, col)) if !filename.is_empty() => Self {
+                filename,
+                position: Some(Position {
+                    x: col.saturating_sub(1),
+                    y: line.saturating_sub(1),
+                }),
+            },
+            _ => plain,
+        }
     }
 }
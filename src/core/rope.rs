@@ -0,0 +1,713 @@
+use super::utils;
+use std::{
+    cell::Cell,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// 每个分块的最大字节数, 分块只在字位边界处切分(单个字位超过这个长度时分块会更长)
+const MAX_CHUNK_LEN: usize = 1024;
+/// 编辑后短于这个长度的分块会与后面的分块合并, 防止逐字输入时产生大量很小的分块
+const MIN_CHUNK_LEN: usize = 256;
+
+type Tree = Option<Box<Node>>;
+
+/// 一行的文本
+/// 文本在字位边界处切分为多个分块, 分块按顺序保存在treap(按随机优先级保持平衡的二叉树)中,
+/// 每个节点缓存子树的字节数, 字位数以及显示宽度, 因此按字位或字节定位, 插入, 删除以及计算显示列
+/// 都只需要O(log n), 编辑很长的行(如压缩过的代码)时不需要复制或重新分割整行
+#[derive(Debug, Default)]
+pub struct Rope {
+    root: Tree,
+}
+
+#[derive(Debug)]
+struct Node {
+    chunk: String,
+    chunk_graphemes: usize,
+    bytes: usize,     // 子树的字节数
+    graphemes: usize, // 子树的字位数
+    priority: u64,
+    // 分块和子树的显示宽度, 取决于制表符宽度, 第一次使用时才计算
+    chunk_width: Cell<Option<Width>>,
+    width: Cell<Option<Width>>,
+    left: Tree,
+    right: Tree,
+}
+
+/// 一段文本的显示宽度
+/// 制表符的宽度取决于所在的列, 因此分别记录第一个制表符之前的宽度, 以及第一个制表符之后(从制表位开始)的宽度,
+/// 两段文本的宽度可以直接合并, 不需要重新计算每个字位
+#[derive(Debug, Clone, Copy)]
+struct Width {
+    tab_width: usize,
+    before_tab: usize,
+    after_tab: Option<usize>,
+}
+
+impl Width {
+    fn new(tab_width: usize, before_tab: usize, after_tab: Option<usize>) -> Self {
+        Self {
+            tab_width,
+            before_tab,
+            after_tab,
+        }
+    }
+
+    /// 一个字位的宽度
+    fn of(grapheme: &str, tab_width: usize) -> Self {
+        if grapheme == "\t" {
+            Self::new(tab_width, 0, Some(0))
+        } else {
+            Self::new(tab_width, utils::grapheme_width(grapheme), None)
+        }
+    }
+
+    /// 从column列开始, 经过这段文本后所在的列
+    fn advance(&self, column: usize) -> usize {
+        match self.after_tab {
+            None => column + self.before_tab,
+            Some(after) => {
+                let column = column + self.before_tab;
+                column - column % self.tab_width + self.tab_width + after
+            }
+        }
+    }
+
+    /// 这段文本之后紧接着next时的宽度
+    /// 第一个制表符之后的部分从制表位开始, 因此next的位置也只取决于这部分的宽度
+    fn then(self, next: Self) -> Self {
+        match self.after_tab {
+            None => Self::new(
+                self.tab_width,
+                self.before_tab + next.before_tab,
+                next.after_tab,
+            ),
+            Some(after) => Self::new(self.tab_width, self.before_tab, Some(next.advance(after))),
+        }
+    }
+}
+
+impl Node {
+    fn new(chunk: String) -> Box<Self> {
+        let chunk_graphemes = grapheme_count(&chunk);
+        Box::new(Self {
+            bytes: chunk.len(),
+            graphemes: chunk_graphemes,
+            chunk,
+            chunk_graphemes,
+            priority: priority(),
+            chunk_width: Cell::new(None),
+            width: Cell::new(None),
+            left: None,
+            right: None,
+        })
+    }
+
+    /// 子节点变化后重新计算子树的统计信息
+    fn update(&mut self) {
+        self.bytes = bytes(&self.left) + self.chunk.len() + bytes(&self.right);
+        self.graphemes = graphemes(&self.left) + self.chunk_graphemes + graphemes(&self.right);
+        self.width.set(None);
+    }
+
+    /// 分块的显示宽度
+    fn chunk_width(&self, tab_width: usize) -> Width {
+        if let Some(width) = self.chunk_width.get().filter(|w| w.tab_width == tab_width) {
+            return width;
+        }
+        let width = self
+            .chunk
+            .graphemes(true)
+            .fold(Width::new(tab_width, 0, None), |width, grapheme| {
+                width.then(Width::of(grapheme, tab_width))
+            });
+        self.chunk_width.set(Some(width));
+        width
+    }
+
+    /// 子树的显示宽度
+    fn width(&self, tab_width: usize) -> Width {
+        if let Some(width) = self.width.get().filter(|w| w.tab_width == tab_width) {
+            return width;
+        }
+        let width = width(&self.left, tab_width)
+            .then(self.chunk_width(tab_width))
+            .then(width(&self.right, tab_width));
+        self.width.set(Some(width));
+        width
+    }
+}
+
+impl From<&str> for Rope {
+    fn from(text: &str) -> Self {
+        Self { root: build(text) }
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chunks().try_for_each(|chunk| f.write_str(chunk))
+    }
+}
+
+impl Rope {
+    /// 字位数
+    pub fn len(&self) -> usize {
+        graphemes(&self.root)
+    }
+
+    /// 按顺序遍历所有分块
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks::from(&self.root, 0).0
+    }
+
+    /// 从第at个字位开始遍历字位
+    pub fn graphemes(&self, at: usize) -> impl Iterator<Item = &str> {
+        let (chunks, skip) = Chunks::from(&self.root, at);
+        chunks.flat_map(|chunk| chunk.graphemes(true)).skip(skip)
+    }
+
+    /// 从第at个字位开始的len个字位
+    pub fn slice(&self, at: usize, len: usize) -> String {
+        self.graphemes(at).take(len).collect()
+    }
+
+    /// 字位下标转换为字节下标, 超出长度时返回字节长度
+    pub fn byte_index(&self, index: usize) -> usize {
+        let mut node = &self.root;
+        let mut index = index;
+        let mut result = 0;
+        while let Some(current) = node {
+            let left = graphemes(&current.left);
+            if index <= left {
+                node = &current.left;
+                continue;
+            }
+            result += bytes(&current.left);
+            index -= left;
+            if index < current.chunk_graphemes {
+                return result + grapheme_offset(&current.chunk, index);
+            }
+            result += current.chunk.len();
+            index -= current.chunk_graphemes;
+            node = &current.right;
+        }
+        result
+    }
+
+    /// 字节下标转换为字位下标(字节下标落在字位中间时返回下一个字位)
+    pub fn grapheme_index(&self, byte_index: usize) -> usize {
+        let mut node = &self.root;
+        let mut byte_index = byte_index;
+        let mut result = 0;
+        while let Some(current) = node {
+            let left = bytes(&current.left);
+            if byte_index <= left {
+                node = &current.left;
+                continue;
+            }
+            result += graphemes(&current.left);
+            byte_index -= left;
+            if byte_index < current.chunk.len() {
+                return result
+                    + current
+                        .chunk
+                        .grapheme_indices(true)
+                        .take_while(|(offset, _)| *offset < byte_index)
+                        .count();
+            }
+            result += current.chunk_graphemes;
+            byte_index -= current.chunk.len();
+            node = &current.right;
+        }
+        result
+    }
+
+    /// 第index个字位所在的显示列(即前面所有字位的显示宽度之和)
+    pub fn column_of(&self, index: usize, tab_width: usize) -> usize {
+        let mut node = &self.root;
+        let mut index = index;
+        let mut column = 0;
+        while let Some(current) = node {
+            let left = graphemes(&current.left);
+            if index <= left {
+                node = &current.left;
+                continue;
+            }
+            column = width(&current.left, tab_width).advance(column);
+            index -= left;
+            if index < current.chunk_graphemes {
+                return current
+                    .chunk
+                    .graphemes(true)
+                    .take(index)
+                    .fold(column, |column, grapheme| {
+                        Width::of(grapheme, tab_width).advance(column)
+                    });
+            }
+            column = current.chunk_width(tab_width).advance(column);
+            index -= current.chunk_graphemes;
+            node = &current.right;
+        }
+        column
+    }
+
+    /// 显示列所在的字位下标, 列落在宽字符或制表符中间时返回该字符的下标, 超出行宽时返回长度
+    pub fn index_of(&self, column: usize, tab_width: usize) -> usize {
+        let mut node = &self.root;
+        let mut current_column = 0;
+        let mut result = 0;
+        while let Some(current) = node {
+            let after_left = width(&current.left, tab_width).advance(current_column);
+            if after_left > column {
+                node = &current.left;
+                continue;
+            }
+            current_column = after_left;
+            result += graphemes(&current.left);
+            let after_chunk = current.chunk_width(tab_width).advance(current_column);
+            if after_chunk > column {
+                for grapheme in current.chunk.graphemes(true) {
+                    current_column = Width::of(grapheme, tab_width).advance(current_column);
+                    if current_column > column {
+                        return result;
+                    }
+                    result += 1;
+                }
+            }
+            current_column = after_chunk;
+            result += current.chunk_graphemes;
+            node = &current.right;
+        }
+        result
+    }
+
+    /// 从第at个字位开始, 将len个字位替换为text
+    /// 只重新分割编辑位置所在的分块(以及前一个字位所在的分块), 之后的字位边界发生变化时(如插入组合字符,
+    /// 区域指示符重新配对)继续合并后面的分块, 直到连接处仍是原来的字位边界
+    pub fn replace(&mut self, at: usize, len: usize, text: &str) {
+        let at = at.min(self.len());
+        let end = at.saturating_add(len).min(self.len());
+        // 编辑位置之前的字位边界不会改变, 从前一个字位所在分块的开头开始重新分割
+        let first = self.chunk_range(at.saturating_sub(1)).0;
+        let last = self.chunk_range(end).1;
+        let (left, rest) = split(self.root.take(), first);
+        let (middle, right) = split(rest, last - first);
+        let mut middle = Rope { root: middle }.to_string();
+        let start_byte = grapheme_offset(&middle, at - first);
+        let end_byte = start_byte + grapheme_offset(&middle[start_byte..], end - at);
+        middle.replace_range(start_byte..end_byte, text);
+        self.root = join(left, middle, right);
+    }
+
+    /// 在第at个字位处分为两部分, 返回后一部分
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let (left, right) = split(self.root.take(), at);
+        self.root = left;
+        Self { root: right }
+    }
+
+    /// 在末尾追加另一段文本
+    pub fn append(&mut self, other: Self) {
+        let len = self.len();
+        if len == 0 {
+            self.root = other.root;
+            return;
+        }
+        let first = self.chunk_range(len - 1).0;
+        let (left, last) = split(self.root.take(), first);
+        let last = Rope { root: last }.to_string();
+        self.root = join(left, last, other.root);
+    }
+
+    /// 第index个字位所在分块的字位范围, 超出长度时返回(长度, 长度)
+    fn chunk_range(&self, index: usize) -> (usize, usize) {
+        let mut node = &self.root;
+        let mut index = index;
+        let mut start = 0;
+        while let Some(current) = node {
+            let left = graphemes(&current.left);
+            if index < left {
+                node = &current.left;
+                continue;
+            }
+            start += left;
+            index -= left;
+            if index < current.chunk_graphemes {
+                return (start, start + current.chunk_graphemes);
+            }
+            start += current.chunk_graphemes;
+            index -= current.chunk_graphemes;
+            node = &current.right;
+        }
+        (start, start)
+    }
+}
+
+/// 按顺序遍历分块, 栈中保存还没有访问的节点(之后还要访问其分块和右子树)
+pub struct Chunks<'a> {
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Chunks<'a> {
+    /// 从第at个字位所在的分块开始遍历, 同时返回at在该分块中的字位下标
+    fn from(tree: &'a Tree, at: usize) -> (Self, usize) {
+        let mut stack = Vec::new();
+        let mut node = tree;
+        let mut at = at;
+        while let Some(current) = node {
+            let left = graphemes(&current.left);
+            if at < left {
+                stack.push(&**current);
+                node = &current.left;
+            } else if at - left < current.chunk_graphemes {
+                stack.push(&**current);
+                return (Self { stack }, at - left);
+            } else {
+                at -= left + current.chunk_graphemes;
+                node = &current.right;
+            }
+        }
+        (Self { stack }, 0)
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.stack.pop()?;
+        let mut node = &current.right;
+        while let Some(next) = node {
+            self.stack.push(next);
+            node = &next.left;
+        }
+        Some(&current.chunk)
+    }
+}
+
+fn bytes(tree: &Tree) -> usize {
+    tree.as_ref().map_or(0, |node| node.bytes)
+}
+
+fn graphemes(tree: &Tree) -> usize {
+    tree.as_ref().map_or(0, |node| node.graphemes)
+}
+
+fn width(tree: &Tree, tab_width: usize) -> Width {
+    tree.as_ref()
+        .map_or(Width::new(tab_width, 0, None), |node| node.width(tab_width))
+}
+
+/// 是否只有ASCII字符并且没有\r\n, 此时每个字节都是一个字位, 不需要分割
+fn is_simple(text: &str) -> bool {
+    text.is_ascii() && !text.contains("\r\n")
+}
+
+/// 字位数
+fn grapheme_count(text: &str) -> usize {
+    if is_simple(text) {
+        text.len()
+    } else {
+        text.graphemes(true).count()
+    }
+}
+
+/// 第index个字位在text中的字节下标, 超出长度时返回text的长度
+fn grapheme_offset(text: &str, index: usize) -> usize {
+    if is_simple(text) {
+        return index.min(text.len());
+    }
+    text.grapheme_indices(true)
+        .nth(index)
+        .map_or(text.len(), |(offset, _)| offset)
+}
+
+/// text中的offset处(前后都是完整的字位)是否仍是字位边界
+/// 两侧都是ASCII字符时只有\r\n不能分开, 其他情况重新分割text
+fn is_boundary(text: &str, offset: usize) -> bool {
+    let before = text[..offset].chars().next_back();
+    let after = text[offset..].chars().next();
+    match (before, after) {
+        (None, _) | (_, None) => true,
+        (Some(before), Some(after)) if before.is_ascii() && after.is_ascii() => {
+            !(before == '\r' && after == '\n')
+        }
+        _ => text
+            .grapheme_indices(true)
+            .any(|(boundary, _)| boundary == offset),
+    }
+}
+
+/// 节点的随机优先级(splitmix64), 优先级高的节点在上, 使树的高度期望为O(log n)
+fn priority() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let mut z = NEXT.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// 将文本按字位边界切分为分块并构建树
+fn build(text: &str) -> Tree {
+    if is_simple(text) {
+        return (0..text.len())
+            .step_by(MAX_CHUNK_LEN)
+            .fold(None, |tree, start| {
+                let end = (start + MAX_CHUNK_LEN).min(text.len());
+                merge(tree, Some(Node::new(text[start..end].to_string())))
+            });
+    }
+    let mut tree = None;
+    let mut start = 0;
+    let mut end = 0;
+    for (offset, grapheme) in text.grapheme_indices(true) {
+        if offset + grapheme.len() - start > MAX_CHUNK_LEN && end > start {
+            tree = merge(tree, Some(Node::new(text[start..end].to_string())));
+            start = end;
+        }
+        end = offset + grapheme.len();
+    }
+    if end > start {
+        tree = merge(tree, Some(Node::new(text[start..end].to_string())));
+    }
+    tree
+}
+
+/// 连接两棵树, left中的分块都在right之前
+fn merge(left: Tree, right: Tree) -> Tree {
+    match (left, right) {
+        (None, tree) | (tree, None) => tree,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                left.right = merge(left.right.take(), Some(right));
+                left.update();
+                Some(left)
+            } else {
+                right.left = merge(Some(left), right.left.take());
+                right.update();
+                Some(right)
+            }
+        }
+    }
+}
+
+/// 在第at个字位处将树分为两棵, at落在分块中间时切分该分块
+fn split(tree: Tree, at: usize) -> (Tree, Tree) {
+    let Some(mut node) = tree else {
+        return (None, None);
+    };
+    let left = graphemes(&node.left);
+    if at <= left {
+        let (first, second) = split(node.left.take(), at);
+        node.left = second;
+        node.update();
+        (first, Some(node))
+    } else if at >= left + node.chunk_graphemes {
+        let (first, second) = split(node.right.take(), at - left - node.chunk_graphemes);
+        node.right = first;
+        node.update();
+        (Some(node), second)
+    } else {
+        let index = at - left;
+        let rest = node.chunk.split_off(grapheme_offset(&node.chunk, index));
+        node.chunk_graphemes = index;
+        node.chunk_width.set(None);
+        let right = node.right.take();
+        node.update();
+        (Some(node), merge(Some(Node::new(rest)), right))
+    }
+}
+
+/// 连接left, middle和right, middle从字位边界开始
+/// middle与right的连接处不再是字位边界时(或middle太短时), 将right的分块依次并入middle后重新分割
+fn join(left: Tree, mut middle: String, mut right: Tree) -> Tree {
+    while let Some(len) = first_chunk_graphemes(&right) {
+        let (chunk, rest) = split(right, len);
+        let joined = middle.len();
+        if let Some(chunk) = &chunk {
+            middle.push_str(&chunk.chunk);
+        }
+        if joined >= MIN_CHUNK_LEN && is_boundary(&middle, joined) {
+            middle.truncate(joined);
+            right = merge(chunk, rest);
+            break;
+        }
+        right = rest;
+    }
+    merge(merge(left, build(&middle)), right)
+}
+
+/// 第一个分块的字位数, 树为空时返回None
+fn first_chunk_graphemes(tree: &Tree) -> Option<usize> {
+    let mut node = tree.as_ref()?;
+    while let Some(left) = &node.left {
+        node = left;
+    }
+    Some(node.chunk_graphemes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 简单的伪随机数(线性同余), 测试结果可以重现
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) % n as u64) as usize
+        }
+    }
+
+    /// 随机的文本, 包括组合字符, 表情修饰符, 国旗(区域指示符成对组成字位), 零宽连接符以及宽字符
+    fn random_text(random: &mut Random, max_len: usize) -> String {
+        const PIECES: [&str; 12] = [
+            "a",
+            "b",
+            " ",
+            "\t",
+            "e\u{301}",
+            "\u{301}",
+            "\u{1f44d}",
+            "\u{1f3fd}",
+            "\u{1f1e8}",
+            "\u{200d}",
+            "\u{4e2d}",
+            "\r",
+        ];
+        (0..random.below(max_len + 1))
+            .map(|_| PIECES[random.below(PIECES.len())])
+            .collect()
+    }
+
+    /// 检查树的结构: 每个分块都从字位边界(boundaries)开始, 缓存的字节数和字位数与分块一致, 返回树的高度
+    fn check(tree: &Tree, text: &str, boundaries: &[usize], start: usize) -> usize {
+        let Some(node) = tree else {
+            return 0;
+        };
+        let left = check(&node.left, text, boundaries, start);
+        let chunk_start = start + bytes(&node.left);
+        let chunk_end = chunk_start + node.chunk.len();
+        assert!(!node.chunk.is_empty());
+        assert_eq!(&text[chunk_start..chunk_end], node.chunk);
+        assert!(boundaries.binary_search(&chunk_start).is_ok());
+        assert_eq!(node.chunk_graphemes, node.chunk.graphemes(true).count());
+        let right = check(&node.right, text, boundaries, chunk_end);
+        assert_eq!(node.bytes, chunk_end - start + bytes(&node.right));
+        assert_eq!(
+            node.graphemes,
+            graphemes(&node.left) + node.chunk_graphemes + graphemes(&node.right)
+        );
+        1 + left.max(right)
+    }
+
+    /// 检查整棵树, 返回树的高度
+    fn check_tree(rope: &Rope, text: &str) -> usize {
+        let boundaries: Vec<usize> = text
+            .grapheme_indices(true)
+            .map(|(offset, _)| offset)
+            .collect();
+        check(&rope.root, text, &boundaries, 0)
+    }
+
+    /// 与逐个字位计算的结果比较, 很长的文本只比较部分字位
+    fn check_rope(rope: &Rope, text: &str) {
+        assert_eq!(rope.to_string(), text);
+        check_tree(rope, text);
+        let graphemes: Vec<(usize, &str)> = text.grapheme_indices(true).collect();
+        assert_eq!(rope.len(), graphemes.len(), "text {:?}", text);
+        let step = graphemes.len() / 20 + 1;
+        let mut column = 0;
+        for (index, (offset, grapheme)) in graphemes.iter().enumerate() {
+            let width = if *grapheme == "\t" {
+                4 - column % 4
+            } else {
+                utils::grapheme_width(grapheme)
+            };
+            if index % step == 0 || index + 3 > graphemes.len() {
+                assert_eq!(rope.byte_index(index), *offset);
+                assert_eq!(rope.grapheme_index(*offset), index);
+                assert_eq!(rope.grapheme_index(offset + 1), index + 1);
+                assert_eq!(rope.column_of(index, 4), column);
+                assert_eq!(rope.graphemes(index).next(), Some(*grapheme));
+                // 零宽的字位与后面的字位在同一列, 该列对应后面的字位
+                if width > 0 {
+                    assert_eq!(rope.index_of(column, 4), index);
+                }
+            }
+            column += width;
+        }
+        assert_eq!(rope.column_of(graphemes.len(), 4), column);
+        assert_eq!(rope.index_of(column, 4), graphemes.len());
+        assert_eq!(rope.byte_index(graphemes.len()), text.len());
+    }
+
+    /// 在text中替换字位, 作为比较的基准
+    fn replace(text: &str, at: usize, len: usize, inserted: &str) -> String {
+        let graphemes: Vec<&str> = text.graphemes(true).collect();
+        let at = at.min(graphemes.len());
+        let end = at.saturating_add(len).min(graphemes.len());
+        [&graphemes[..at], &[inserted], &graphemes[end..]]
+            .concat()
+            .concat()
+    }
+
+    #[test]
+    fn replace_matches_segmentation_of_the_whole_text() {
+        let mut random = Random(2024);
+        for round in 0..40 {
+            // 一半的行足够长, 会分成多个分块
+            let max_len = if round % 2 == 0 { 12 } else { 1500 };
+            let mut text = random_text(&mut random, max_len);
+            let mut rope = Rope::from(text.as_str());
+            check_rope(&rope, &text);
+            for _ in 0..20 {
+                let at = random.below(rope.len() + 2);
+                let len = random.below(4);
+                let inserted = random_text(&mut random, 4);
+                rope.replace(at, len, &inserted);
+                text = replace(&text, at, len, &inserted);
+                check_rope(&rope, &text);
+            }
+        }
+    }
+
+    #[test]
+    fn split_off_and_append_keep_the_text() {
+        let mut random = Random(7);
+        for _ in 0..50 {
+            let text = random_text(&mut random, 1500);
+            let mut rope = Rope::from(text.as_str());
+            let at = random.below(rope.len() + 1);
+            let rest = rope.split_off(at);
+            let graphemes: Vec<&str> = text.graphemes(true).collect();
+            check_rope(&rope, &graphemes[..at].concat());
+            check_rope(&rest, &graphemes[at..].concat());
+
+            // 连接后字位可能合并(如组合字符接在末尾字母之后)
+            let other = random_text(&mut random, 1500);
+            rope.append(Rope::from(other.as_str()));
+            check_rope(&rope, &(graphemes[..at].concat() + &other));
+        }
+    }
+
+    #[test]
+    fn typing_into_a_long_line_keeps_the_tree_shallow() {
+        let mut rope = Rope::from("x".repeat(1 << 20).as_str());
+        let mut random = Random(1);
+        for _ in 0..10_000 {
+            let at = random.below(rope.len() + 1);
+            rope.replace(at, 0, "y");
+        }
+        assert_eq!(rope.len(), (1 << 20) + 10_000);
+        let height = check_tree(&rope, &rope.to_string());
+        assert!(height < 64, "height {}", height);
+    }
+}
@@ -1,101 +1,407 @@
+use super::{
+    editor::SearchDirection,
+    filetype::HighlightingOptions,
+    highlighting::Type,
+    rope::{Chunks, Rope},
+    utils,
+};
+use std::fmt;
 use unicode_segmentation::UnicodeSegmentation;
 
+/// 只高亮每行开头的这么多个字位, 很长的行(如压缩过的代码)编辑后不需要重新高亮整行
+const MAX_HIGHLIGHT_LEN: usize = 10_000;
+
 #[derive(Debug, Default)]
 pub struct Row {
-    text: String,
-    len: usize,
+    text: Rope,
+    highlighting: Vec<Type>, // 每个字位的高亮类型(只包括开头的MAX_HIGHLIGHT_LEN个字位)
+    // 上次高亮时的状态(开始时是否在多行注释中, 结束时是否在多行注释中), 为None时需要重新高亮
+    highlight_state: Option<(bool, bool)>,
 }
 
 impl From<&str> for Row {
     fn from(value: &str) -> Self {
-        let mut row = Self {
-            text: String::from(value),
-            len: 0,
-        };
-        row.update_len();
-        row
+        Self {
+            text: Rope::from(value),
+            ..Self::default()
+        }
+    }
+}
+
+impl fmt::Display for Row {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.text.fmt(f)
     }
 }
+
 impl Row {
     /// 渲染文本
-    /// end: 实际值如果大于终端长度,那么就用终端长度. 否则就是实际值(防止值超出范围)
-    pub fn render(&self, start: usize, end: usize) -> String {
-        let end = std::cmp::min(end, self.text.len());
-        let start = std::cmp::min(start, end);
-        self.text.get(start..end).unwrap_or_default().to_string();
-        let mut result = String::new();
+    /// start, end表示显示列(而不是字位下标), 渲染的是显示在[start, end)列之间的内容
+    /// 制表符展开为空格直到下一个制表位; 宽字符被start或end截断时, 被截断的部分用空格代替
+    /// 返回值按高亮类型分段, 相邻且高亮类型相同的字位合并为一段
+    /// 从显示在start列的字位开始渲染, 不需要从行首开始计算宽度
+    pub fn render(&self, start: usize, end: usize, tab_width: usize) -> Vec<(Type, String)> {
+        let mut result: Vec<(Type, String)> = Vec::new();
+        let first = self.text.index_of(start, tab_width);
+        let mut column = self.text.column_of(first, tab_width);
         // graphemes 表示字位(光标移动的最小单位)
-        // skip表示跳过几个(实际上就是第几个开始)
-        // take表示几个字位长度
-        for grapheme in self.text[..].graphemes(true).skip(start).take(end - start) {
-            if grapheme == "\t" {
-                result.push_str("  ");
+        for (index, grapheme) in (first..).zip(self.text.graphemes(first)) {
+            if column >= end {
+                break;
+            }
+            let width = grapheme_width(grapheme, column, tab_width);
+            let next = column + width;
+            let rendered = if grapheme == "\t" || column < start || next > end {
+                // 只显示在[start, end)之间的部分
+                " ".repeat(next.min(end) - column.max(start))
             } else {
-                result.push_str(grapheme)
+                grapheme.to_string()
+            };
+            column = next;
+            let highlighting_type = self.highlighting.get(index).copied().unwrap_or_default();
+            match result.last_mut() {
+                Some((last_type, text)) if *last_type == highlighting_type => {
+                    text.push_str(&rendered)
+                }
+                _ => result.push((highlighting_type, rendered)),
             }
         }
         result
     }
-    /// 插入字符
-    pub fn insert(&mut self, at: usize, c: char) {
-        if at >= self.len() {
-            self.text.push(c);
+
+    /// 第index个字位所在的显示列(即前面所有字位的显示宽度之和)
+    pub fn column_of(&self, index: usize, tab_width: usize) -> usize {
+        self.text.column_of(index, tab_width)
+    }
+
+    /// 显示列所在的字位下标, 列落在宽字符或制表符中间时返回该字符的下标, 超出行宽时返回行长度
+    pub fn index_of(&self, column: usize, tab_width: usize) -> usize {
+        self.text.index_of(column, tab_width)
+    }
+
+    /// 语法高亮
+    /// start_in_comment表示该行开始时是否处于多行注释中, 返回值表示该行结束时是否处于多行注释中
+    /// 分段的文件(如diff)没有多行注释, 此时表示是否处于段(hunk)中
+    /// 文本和开始状态都没有变化时直接使用上次的结果
+    /// 只高亮开头的MAX_HIGHLIGHT_LEN个字位, 多行注释的状态也以此为准
+    pub fn highlight(&mut self, options: &HighlightingOptions, start_in_comment: bool) -> bool {
+        if let Some((start, end)) = self.highlight_state {
+            if start == start_in_comment {
+                return end;
+            }
+        }
+        let text = self.text.slice(0, MAX_HIGHLIGHT_LEN);
+        let graphemes: Vec<(usize, &str)> = text.grapheme_indices(true).collect();
+        let mut highlighting = vec![Type::None; graphemes.len()];
+        let mut in_comment = start_in_comment;
+
+        // 分段的文件: 判断该行是否在段内, 段外的行先检查只在段外使用的前缀(如diff的文件头)
+        let mut in_section = false;
+        let mut headers: &[(&str, Type)] = &[];
+        if let Some(sections) = options.sections() {
+            in_section = text.starts_with(sections.start)
+                || (start_in_comment
+                    && (text.is_empty()
+                        || sections.lines.iter().any(|line| text.starts_with(line))));
+            in_comment = false;
+            if !in_section {
+                headers = sections.headers;
+            }
+        }
+
+        // 整行高亮(分段的文件中行首的空白有意义, 如diff中的上下文行)
+        let trimmed = if options.sections().is_some() {
+            &text[..]
         } else {
-            // result 获取的是前面的at之前的字符, remainder是获取at之后的字符
-            let mut result: String = self.text[..].graphemes(true).take(at).collect();
-            let remainder: String = self.text[..].graphemes(true).skip(at).collect();
-            result.push(c);
-            result.push_str(&remainder);
-            self.text = result;
+            text.trim_start()
+        };
+        if let Some((_, marker_type)) = headers
+            .iter()
+            .chain(options.line_markers())
+            .find(|(marker, _)| !in_comment && trimmed.starts_with(marker))
+        {
+            highlighting.fill(*marker_type);
+            self.highlighting = highlighting;
+            self.highlight_state = Some((start_in_comment, in_section));
+            return in_section;
+        }
+
+        let mut index = 0;
+        while let Some(&(byte_index, grapheme)) = graphemes.get(index) {
+            let rest = &text[byte_index..];
+            let previous = index
+                .checked_sub(1)
+                .map_or(Type::None, |previous| highlighting[previous]);
+            let previous_is_separator =
+                index == 0 || graphemes[index - 1].1.chars().all(is_separator);
+
+            // 多行注释
+            if let Some((open, close)) = options.block_comment() {
+                if in_comment || rest.starts_with(open) {
+                    let skip = if in_comment { 0 } else { open.len() };
+                    let (len, closed) = match rest[skip..].find(close) {
+                        Some(end) => (skip + end + close.len(), true),
+                        None => (rest.len(), false),
+                    };
+                    let count = rest[..len].graphemes(true).count();
+                    highlighting[index..index + count].fill(Type::Comment);
+                    index += count;
+                    in_comment = !closed;
+                    continue;
+                }
+            }
+            // 单行注释
+            if let Some(prefix) = options.line_comment() {
+                if rest.starts_with(prefix) {
+                    highlighting[index..].fill(Type::Comment);
+                    break;
+                }
+            }
+            // 字符串
+            if let Some(quote) = grapheme
+                .chars()
+                .next()
+                .filter(|c| options.quotes().contains(c))
+            {
+                let mut escaped = false;
+                let mut len = rest.len();
+                for (offset, c) in rest.char_indices().skip(1) {
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == quote {
+                        len = offset + c.len_utf8();
+                        break;
+                    }
+                }
+                let count = rest[..len].graphemes(true).count();
+                highlighting[index..index + count].fill(Type::String);
+                index += count;
+                continue;
+            }
+            // 数字
+            if options.numbers() {
+                let is_digit = grapheme.chars().all(|c| c.is_ascii_digit());
+                if (is_digit && (previous_is_separator || previous == Type::Number))
+                    || (grapheme == "." && previous == Type::Number)
+                {
+                    highlighting[index] = Type::Number;
+                    index += 1;
+                    continue;
+                }
+            }
+            // 关键字和类型
+            if previous_is_separator {
+                if let Some((len, word_type)) = Self::match_word(rest, options) {
+                    highlighting[index..index + len].fill(word_type);
+                    index += len;
+                    continue;
+                }
+            }
+            index += 1;
+        }
+
+        self.highlighting = highlighting;
+        self.highlight_state = Some((start_in_comment, in_comment || in_section));
+        in_comment || in_section
+    }
+
+    /// 检查text是否以关键字或类型开头(后面紧跟分隔符或行尾), 返回匹配的长度和高亮类型
+    fn match_word(text: &str, options: &HighlightingOptions) -> Option<(usize, Type)> {
+        let words = options
+            .keywords()
+            .iter()
+            .map(|word| (word, Type::Keyword))
+            .chain(options.types().iter().map(|word| (word, Type::Primitive)));
+        for (word, word_type) in words {
+            if let Some(after) = text.strip_prefix(word) {
+                let is_boundary = after.chars().next().is_none_or(is_separator);
+                if is_boundary {
+                    return Some((word.len(), word_type));
+                }
+            }
         }
-        self.update_len();
+        None
+    }
+
+    /// 插入字符串
+    pub fn insert(&mut self, at: usize, text: &str) {
+        self.replace(at.min(self.len()), 0, text)
     }
     /// 删除字符
-    /// at表示x坐标,len表示行长度
-    /// 当x大于行长度时
-    pub fn delete(&mut self, at: usize) {
+    /// 从第at个字位开始删除len个字位, 返回被删除的内容
+    /// 当at大于等于行长度时不删除任何内容
+    pub fn delete(&mut self, at: usize, len: usize) -> String {
         if at >= self.len() {
-            return;
-        } else {
-            // result 获取的是前面的at之前的字符, remainder是获取at之后的字符
-            let mut result: String = self.text[..].graphemes(true).take(at).collect();
-            let remainder: String = self.text[..].graphemes(true).skip(at + 1).collect();
-            result.push_str(&remainder);
-            self.text = result;
+            return String::new();
         }
-        self.update_len()
+        let removed = self.slice(at, len);
+        self.replace(at, len, "");
+        removed
     }
 
     /// 分割字符串(回车创建一个新行)
     /// 如果是在行中间, 那么就将当前行分为两部分, 第一部分作为新的当前行, 另一部分作为新行返回出去
     /// 如果是在行皆为, 那么当前行不发生变化, 会有一个空的字符串作为新行返回出去
     pub fn split(&mut self, at: usize) -> Self {
-        let begining: String = self.text[..].graphemes(true).take(at).collect();
-        let remainder: String = self.text[..].graphemes(true).skip(at).collect();
-        self.text = begining;
-        self.update_len();
-        Self::from(&remainder[..])
+        let remainder = self.text.split_off(at);
+        self.reset_highlighting();
+        Self {
+            text: remainder,
+            ..Self::default()
+        }
     }
 
     /// 追加新的行
-    pub fn append(&mut self, new: &Self) {
-        self.text = format!("{}{}", self.text, new.text);
-        self.update_len()
+    pub fn append(&mut self, new: Self) {
+        self.text.append(new.text);
+        self.reset_highlighting();
+    }
+
+    /// 查找字符串
+    /// at表示从第几个字位开始查找, 向前查找时范围是[at, len), 向后查找时范围是[0, at)
+    /// 返回值是匹配位置的字位下标(而不是字节下标)
+    pub fn find(&self, query: &str, at: usize, direction: SearchDirection) -> Option<usize> {
+        if at > self.len() || query.is_empty() {
+            return None;
+        }
+        let (start, end) = match direction {
+            SearchDirection::Forward => (at, self.len()),
+            SearchDirection::Backward => (0, at),
+        };
+        let substring = self.text.slice(start, end - start);
+        let matching_byte_index = match direction {
+            SearchDirection::Forward => substring.find(query),
+            SearchDirection::Backward => substring.rfind(query),
+        }?;
+        // 将字节下标转换为字位下标, 匹配位置落在字位中间时视为未找到
+        substring
+            .grapheme_indices(true)
+            .position(|(offset, _)| offset == matching_byte_index)
+            .map(|index| start + index)
+    }
+
+    /// 替换字符串
+    /// 从第at个字位开始, 将len个字位替换为text
+    pub fn replace(&mut self, at: usize, len: usize, text: &str) {
+        self.text.replace(at, len, text);
+        self.reset_highlighting();
+    }
+
+    /// 获取从第at个字位开始的len个字位
+    pub fn slice(&self, at: usize, len: usize) -> String {
+        self.text.slice(at, len)
+    }
+
+    /// 字节下标转换为字位下标(字节下标落在字位中间时返回下一个字位)
+    pub fn grapheme_index(&self, byte_index: usize) -> usize {
+        self.text.grapheme_index(byte_index)
     }
 
-    /// 转换为字节流
-    pub fn as_bytes(&self) -> &[u8] {
-        self.text.as_bytes()
+    /// 字位下标转换为字节下标, 超出行长度时返回行的字节长度
+    pub fn byte_index(&self, index: usize) -> usize {
+        self.text.byte_index(index)
+    }
+
+    /// 按顺序遍历行文本的各个分块
+    pub fn chunks(&self) -> Chunks<'_> {
+        self.text.chunks()
     }
 
     /// 字符串字位长度
     pub fn len(&self) -> usize {
-        self.text[..].graphemes(true).count()
+        self.text.len()
+    }
+    /// 清除高亮缓存, 下次高亮时重新计算
+    pub fn reset_highlighting(&mut self) {
+        self.highlight_state = None;
+    }
+    /// 上次高亮结束时是否处于多行注释(或段)中, 还没有高亮时为false
+    pub fn ends_in_comment(&self) -> bool {
+        self.highlight_state.is_some_and(|(_, end)| end)
+    }
+}
+
+/// 字位的显示宽度, 制表符宽度取决于所在列(到下一个制表位), 其他字位见utils::grapheme_width
+fn grapheme_width(grapheme: &str, column: usize, tab_width: usize) -> usize {
+    if grapheme == "\t" {
+        return tab_width - column % tab_width;
+    }
+    utils::grapheme_width(grapheme)
+}
+
+/// 是否为单词分隔符(下划线属于单词的一部分)
+fn is_separator(c: char) -> bool {
+    (c.is_ascii_punctuation() && c != '_') || c.is_whitespace()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::filetype::FileType;
+
+    /// 依次高亮多行(传递多行状态), 返回每行第一个字位的高亮类型
+    fn highlight_lines(file_name: &str, lines: &[&str]) -> Vec<Type> {
+        let file_type = FileType::from(file_name);
+        let mut state = false;
+        lines
+            .iter()
+            .map(|line| {
+                let mut row = Row::from(*line);
+                state = row.highlight(file_type.highlighting_options(), state);
+                row.highlighting.first().copied().unwrap_or_default()
+            })
+            .collect()
     }
-    pub fn is_empty(&self) -> bool {
-        self.len == 0
+
+    #[test]
+    fn diff_headers_only_outside_hunks() {
+        let types = highlight_lines(
+            "changes.diff",
+            &[
+                "--- a.txt",
+                "+++ b.txt",
+                "@@ -1,3 +1,3 @@",
+                " -- context",
+                "--- removed comment",
+                "+++ added",
+                "",
+                "diff --git a/c b/c",
+                "--- a/c",
+            ],
+        );
+        assert_eq!(
+            types,
+            vec![
+                Type::Keyword,
+                Type::Keyword,
+                Type::Primitive,
+                Type::None,
+                Type::Number,
+                Type::String,
+                Type::None,
+                Type::Keyword,
+                Type::Keyword,
+            ]
+        );
     }
-    fn update_len(&mut self) {
-        self.len = self.text[..].graphemes(true).count()
+
+    #[test]
+    fn render_starts_from_the_requested_column() {
+        let row = Row::from("a\t\u{4e2d}b");
+        // 制表符占1..4列, 中占4..6列
+        assert_eq!(row.render(3, 5, 4), vec![(Type::None, String::from("  "))]);
+        assert_eq!(
+            row.render(4, 7, 4),
+            vec![(Type::None, String::from("\u{4e2d}b"))]
+        );
+
+        // 很长的行只高亮开头部分
+        let mut row = Row::from(format!("// {}", "x".repeat(MAX_HIGHLIGHT_LEN)).as_str());
+        row.highlight(FileType::from("a.rs").highlighting_options(), false);
+        assert_eq!(row.render(0, 1, 4)[0].0, Type::Comment);
+        let end = row.len();
+        assert_eq!(row.render(end - 1, end, 4)[0].0, Type::None);
     }
 }
@@ -0,0 +1,145 @@
+use super::row::Row;
+use std::ops::{Index, IndexMut};
+
+/// 每个分块中的最大行数, 超过时分成两块
+const MAX_CHUNK_LEN: usize = 1024;
+
+/// 文档中所有的行
+/// 行按顺序存放在多个分块中, 并缓存每个分块第一行的行号, 插入和删除行只需要移动所在分块中的行,
+/// 按行号查找时二分查找所在的分块, 这样即使文件有上千万行, 插入和删除行的开销也很小
+/// 每行的文本保存在各自的Rope中(见rope.rs), 打开文件时仍会读入并解码全部内容
+#[derive(Debug, Default)]
+pub struct Rows {
+    chunks: Vec<Vec<Row>>,
+    // starts[i]为第i个分块第一行的行号
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl From<Vec<Row>> for Rows {
+    fn from(rows: Vec<Row>) -> Self {
+        let mut result = Self::default();
+        let mut rows = rows.into_iter().peekable();
+        while rows.peek().is_some() {
+            let chunk: Vec<Row> = rows.by_ref().take(MAX_CHUNK_LEN / 2).collect();
+            result.starts.push(result.len);
+            result.len += chunk.len();
+            result.chunks.push(chunk);
+        }
+        result
+    }
+}
+
+impl Rows {
+    /// 行数
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 获取第index行
+    pub fn get(&self, index: usize) -> Option<&Row> {
+        if index >= self.len {
+            return None;
+        }
+        let (chunk, offset) = self.locate(index);
+        self.chunks[chunk].get(offset)
+    }
+    /// 获取第index行(可修改)
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Row> {
+        if index >= self.len {
+            return None;
+        }
+        let (chunk, offset) = self.locate(index);
+        self.chunks[chunk].get_mut(offset)
+    }
+    /// 最后一行
+    pub fn last(&self) -> Option<&Row> {
+        self.chunks.last().and_then(|chunk| chunk.last())
+    }
+
+    /// 在末尾追加一行
+    pub fn push(&mut self, row: Row) {
+        self.insert(self.len, row)
+    }
+
+    /// 在index处插入一行, index等于行数时追加到末尾
+    pub fn insert(&mut self, index: usize, row: Row) {
+        assert!(index <= self.len, "row index out of bounds");
+        if self.chunks.is_empty() {
+            self.chunks.push(Vec::new());
+            self.starts.push(0);
+        }
+        let (chunk, offset) = if index == self.len {
+            let last = self.chunks.len() - 1;
+            (last, self.chunks[last].len())
+        } else {
+            self.locate(index)
+        };
+        self.chunks[chunk].insert(offset, row);
+        self.len += 1;
+
+        if self.chunks[chunk].len() > MAX_CHUNK_LEN {
+            let second = self.chunks[chunk].split_off(MAX_CHUNK_LEN / 2);
+            self.chunks.insert(chunk + 1, second);
+            self.starts.insert(chunk + 1, 0);
+        }
+        self.update_starts(chunk);
+    }
+
+    /// 删除第index行并返回
+    pub fn remove(&mut self, index: usize) -> Row {
+        assert!(index < self.len, "row index out of bounds");
+        let (chunk, offset) = self.locate(index);
+        let row = self.chunks[chunk].remove(offset);
+        self.len -= 1;
+
+        if self.chunks[chunk].is_empty() {
+            self.chunks.remove(chunk);
+            self.starts.remove(chunk);
+        }
+        self.update_starts(chunk);
+        row
+    }
+
+    /// 按顺序遍历所有行
+    pub fn iter(&self) -> impl Iterator<Item = &Row> {
+        self.chunks.iter().flatten()
+    }
+    /// 按顺序遍历所有行(可修改)
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Row> {
+        self.chunks.iter_mut().flatten()
+    }
+
+    /// 行号所在的分块以及在分块中的位置
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let chunk = self.starts.partition_point(|start| *start <= index) - 1;
+        (chunk, index - self.starts[chunk])
+    }
+
+    /// 从第chunk个分块开始重新计算各分块第一行的行号
+    fn update_starts(&mut self, chunk: usize) {
+        if let Some(first) = self.starts.first_mut() {
+            *first = 0;
+        }
+        for index in chunk.max(1)..self.chunks.len() {
+            self.starts[index] = self.starts[index - 1] + self.chunks[index - 1].len();
+        }
+    }
+}
+
+impl Index<usize> for Rows {
+    type Output = Row;
+
+    fn index(&self, index: usize) -> &Row {
+        self.get(index).expect("row index out of bounds")
+    }
+}
+
+impl IndexMut<usize> for Rows {
+    fn index_mut(&mut self, index: usize) -> &mut Row {
+        self.get_mut(index).expect("row index out of bounds")
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{Error, ErrorKind, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// 交换文件第一行的标记, 之后是写入该文件的进程id
+const MAGIC: &str = "LECTO-SWAP";
+
+/// 交换文件(用于崩溃恢复)
+/// 文档有未保存的修改时, 编辑器定期把文档的全部内容写入文档所在目录下的 .name.lecto-swp,
+/// 保存或正常退出时删除. 编辑器崩溃或终端断开后, 下次打开该文件时可以从交换文件中恢复
+#[derive(Debug)]
+pub struct Swap {
+    pid: u32,
+    contents: String,
+}
+
+impl Swap {
+    /// 文件对应的交换文件路径
+    pub fn path_for(file_name: &str) -> PathBuf {
+        let path = Path::new(file_name);
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        path.with_file_name(format!(".{}.lecto-swp", name))
+    }
+
+    /// 读取交换文件, 文件不存在或格式不正确时返回None
+    pub fn read(path: &Path) -> Option<Self> {
+        let data = fs::read_to_string(path).ok()?;
+        let (header, contents) = data.split_once('\n')?;
+        let pid = header.strip_prefix(MAGIC)?.trim().parse().ok()?;
+        Some(Self {
+            pid,
+            contents: contents.to_string(),
+        })
+    }
+
+    /// 写入交换文件(UTF-8), 先写入临时文件再重命名, 写入过程中崩溃不会破坏上一次的交换文件
+    /// 交换文件只有所有者可以读写(0600), 避免权限受限的文件的内容被其他用户读取
+    pub fn write(path: &Path, contents: &str) -> Result<(), Error> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        // 上次崩溃时残留的临时文件可能有其他权限, 权限只在创建文件时设置
+        Swap::remove(&temporary)?;
+        let result = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&temporary)
+            .and_then(|mut file| {
+                write!(file, "{} {}\n{}", MAGIC, process::id(), contents)?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temporary, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        result
+    }
+
+    /// 删除交换文件(不存在时忽略)
+    pub fn remove(path: &Path) -> Result<(), Error> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// 写入该交换文件的进程id
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+    /// 交换文件中保存的文档内容
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+    /// 写入该交换文件的进程是否仍在运行(另一个编辑器正在编辑同一个文件)
+    pub fn is_owner_running(&self) -> bool {
+        self.pid != process::id() && Path::new(&format!("/proc/{}", self.pid)).exists()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, os::unix::fs::PermissionsExt};
+
+    #[test]
+    fn swap_file_is_private() {
+        let dir = env::temp_dir().join(format!("lecto-swap-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = Swap::path_for(&dir.join("secret.txt").to_string_lossy());
+        // 残留的临时文件不影响新交换文件的权限
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, "stale").unwrap();
+        fs::set_permissions(&temporary, fs::Permissions::from_mode(0o644)).unwrap();
+
+        Swap::write(&path, "password\n").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let swap = Swap::read(&path).unwrap();
+        assert_eq!(swap.contents(), "password\n");
+        assert_eq!(swap.pid(), process::id());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
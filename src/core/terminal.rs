@@ -1,103 +1,336 @@
-use super::editor::Position;
+use super::{
+    backend::{Attributes, RenderTarget, Size},
+    config::Config,
+    editor::Position,
+    error::Error,
+    frame::Frame,
+};
 /// external crate
+use signal_hook::{consts::SIGSTOP, low_level};
 use std::{
-    io::{self, stdout, Write},
-    usize,
+    cell::RefCell,
+    io::{self, stdout, Stdout, Write},
+    panic,
+    sync::{Mutex, Once},
 };
 use termion::{
     color::{self},
+    input::MouseTerminal,
     raw::{IntoRawMode, RawTerminal},
+    screen::{ToAlternateScreen, ToMainScreen},
+    style,
 };
 
-#[derive(Debug)]
-pub struct Size {
-    pub width: u16,
-    pub height: u16,
+/// 正在使用的终端状态, 放在全局变量中以便panic时也能恢复
+static ACTIVE_TERMINAL: Mutex<Option<ActiveTerminal>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+struct ActiveTerminal {
+    _raw: RawTerminal<Stdout>,             // 释放时恢复为进入raw模式前的状态
+    _mouse: Option<MouseTerminal<Stdout>>, // 释放时关闭鼠标事件
+    alternate_screen: bool,                // 是否切换到了备用屏幕
 }
 
+/// 终端
+/// 绘制操作只修改内存中的一帧画面, 输出(flush)时与终端上显示的上一帧比较, 只输出变化的部分,
+/// 并且整帧只写入一次, 避免每次按键都重新输出整个屏幕造成闪烁(特别是通过SSH使用时)
 pub struct Terminal {
-    size: Size,
-    _stdout: RawTerminal<std::io::Stdout>,
+    size: RefCell<Size>,
+    alternate_screen: bool, // 是否使用备用屏幕(退出后恢复原来的屏幕内容)
+    mouse: bool,            // 是否启用鼠标事件
+    frame: RefCell<Frame>,  // 正在绘制的一帧
+    displayed: RefCell<Option<Frame>>, // 终端上当前显示的画面, 为None时下一帧清屏后完整绘制
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        Terminal::restore();
+    }
 }
 
-impl Default for Terminal {
-    fn default() -> Self {
-        let (width, height) = termion::terminal_size().unwrap();
-        Self {
-            size: Size {
+impl Terminal {
+    /// 获取终端大小, 进入raw模式并切换到备用屏幕(设置中关闭时保持在原来的屏幕), 需要时启用鼠标事件
+    /// 同时注册panic钩子: panic时先恢复终端再输出错误信息
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let (width, height) = termion::terminal_size().map_err(Error::Terminal)?;
+        let terminal = Self {
+            size: RefCell::new(Size {
                 width,
                 height: height.saturating_sub(3),
-            },
-            _stdout: stdout().into_raw_mode().unwrap(),
+            }),
+            alternate_screen: config.alternate_screen,
+            mouse: config.mouse,
+            frame: RefCell::new(Frame::new(width as usize, height as usize)),
+            displayed: RefCell::new(None),
+        };
+        terminal.activate()?;
+        PANIC_HOOK.call_once(|| {
+            let previous = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                // 清除编辑器的画面, 避免错误信息与文档内容混在一起
+                print!("{}{}", termion::clear::All, termion::cursor::Goto(1, 1));
+                Terminal::restore();
+                previous(info);
+            }));
+        });
+        Ok(terminal)
+    }
+
+    /// 进入raw模式, 需要时切换到备用屏幕并启用鼠标事件
+    fn activate(&self) -> Result<(), Error> {
+        let raw = stdout().into_raw_mode().map_err(Error::Terminal)?;
+        if self.alternate_screen {
+            print!("{}", ToAlternateScreen);
+        }
+        let mouse = self.mouse.then(|| MouseTerminal::from(stdout()));
+        io::stdout().flush().map_err(Error::Terminal)?;
+        *self.displayed.borrow_mut() = None;
+        *ACTIVE_TERMINAL
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(ActiveTerminal {
+            _raw: raw,
+            _mouse: mouse,
+            alternate_screen: self.alternate_screen,
+        });
+        Ok(())
+    }
+
+    /// 恢复终端: 重置颜色, 显示光标, 关闭鼠标事件, 切换回原来的屏幕并退出raw模式(恢复为进入raw模式前的状态)
+    /// 退出, 出错, 挂起以及panic时都会调用, 已经恢复过时不做任何事
+    pub fn restore() {
+        // panic可能发生在持有锁的时候, 此时锁已经被毒化, 仍然可以取出其中的状态
+        let mut terminal = ACTIVE_TERMINAL
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(active) = terminal.take() {
+            print!(
+                "{}{}{}",
+                color::Fg(color::Reset),
+                color::Bg(color::Reset),
+                termion::cursor::Show
+            );
+            if active.alternate_screen {
+                print!("{}", ToMainScreen);
+            }
+            drop(active);
+            let _ = io::stdout().flush();
         }
     }
 }
 
-impl Terminal {
+impl RenderTarget for Terminal {
+    /// 挂起进程(类似shell中的Ctrl-Z): 先恢复终端再停止进程, 继续运行(fg)后重新进入raw模式
+    fn suspend(&self) -> Result<(), Error> {
+        Terminal::restore();
+        low_level::raise(SIGSTOP).map_err(Error::Terminal)?;
+        self.activate()
+    }
+
     /// 移动光标
-    pub fn cursor_position(&self, position: &Position) {
-        let Position { mut x, mut y } = position;
-        x = x.saturating_add(1);
-        y = y.saturating_add(1);
-        let x = x as u16;
-        let y = y as u16;
-        print!("{}", termion::cursor::Goto(x, y));
+    fn cursor_position(&self, position: &Position) {
+        self.frame
+            .borrow_mut()
+            .set_cursor(position.x(), position.y());
     }
 
-    /// 清空终端屏幕内容
-    pub fn clear_screen(&self) {
-        print!("{}", termion::clear::All)
+    /// 清空终端屏幕内容, 下一帧完整重绘
+    fn clear_screen(&self) {
+        self.frame.borrow_mut().clear();
+        *self.displayed.borrow_mut() = None;
     }
     /// 清空当前行
-    pub fn clear_current_line(&self) {
-        print!("{}", termion::clear::CurrentLine)
+    fn clear_current_line(&self) {
+        self.frame.borrow_mut().clear_line();
     }
 
-    /// 终端绘制字符串
-    pub fn draw_row(&self, text: &str) {
-        println!("{}\r", text)
+    /// 终端绘制字符串(不换行)
+    fn draw_text(&self, text: &str) {
+        self.frame.borrow_mut().draw_text(text);
     }
     /// 终端绘制字符串
-    pub fn draw_row_text_center(&self, prefix: &str, text: &str) {
-        let width = self.size().width as usize;
-        let len = text.len();
-        let padding = width.saturating_sub(len) / 2;
-        let spaces = " ".repeat(padding.saturating_sub(1));
-        self.draw_row(&format!("{}{}{}", prefix, spaces, text))
+    fn draw_row(&self, text: &str) {
+        let mut frame = self.frame.borrow_mut();
+        frame.draw_text(text);
+        frame.new_line();
     }
-
     /// 隐藏光标
-    pub fn cursor_hide(&self) {
-        print!("{}", termion::cursor::Hide)
+    fn cursor_hide(&self) {
+        self.frame.borrow_mut().set_cursor_visible(false);
     }
     /// 显示光标
-    pub fn cursor_show(&self) {
-        print!("{}", termion::cursor::Show)
+    fn cursor_show(&self) {
+        self.frame.borrow_mut().set_cursor_visible(true);
     }
 
     /// 终端大小
-    pub fn size(&self) -> &Size {
-        &self.size
+    fn size(&self) -> Size {
+        *self.size.borrow()
     }
-    /// 刷新输出流
-    pub fn flush(&self) -> Result<(), std::io::Error> {
-        io::stdout().flush()
+    /// 重新获取终端大小(窗口大小变化后调用), 画面按新的大小清空
+    fn update_size(&self) -> Result<(), Error> {
+        let (width, height) = termion::terminal_size().map_err(Error::Terminal)?;
+        *self.size.borrow_mut() = Size {
+            width,
+            height: height.saturating_sub(3),
+        };
+        *self.frame.borrow_mut() = Frame::new(width as usize, height as usize);
+        *self.displayed.borrow_mut() = None;
+        Ok(())
+    }
+    /// 输出一帧: 只输出与上一帧不同的部分, 一次写入终端
+    fn flush(&self) -> Result<(), Error> {
+        let frame = self.frame.borrow();
+        let mut displayed = self.displayed.borrow_mut();
+        let output = render(&frame, displayed.as_ref());
+        let mut stdout = io::stdout().lock();
+        stdout
+            .write_all(output.as_bytes())
+            .and_then(|_| stdout.flush())
+            .map_err(Error::Terminal)?;
+        *displayed = Some(frame.clone());
+        Ok(())
     }
 
     /// 设置背景颜色
-    pub fn set_bg_color(color: color::Rgb) {
-        print!("{}", color::Bg(color))
+    fn set_bg_color(&self, color: color::Rgb) {
+        self.frame.borrow_mut().set_bg(Some(color));
     }
     /// 重置背景颜色
-    pub fn reset_bg_color() {
-        print!("{}", color::Bg(color::Reset))
+    fn reset_bg_color(&self) {
+        self.frame.borrow_mut().set_bg(None);
     }
     /// 设置前景颜色
-    pub fn set_fg_color(color: color::Rgb) {
-        print!("{}", color::Fg(color))
+    fn set_fg_color(&self, color: color::Rgb) {
+        self.frame.borrow_mut().set_fg(Some(color));
     }
     /// 重置前景颜色
-    pub fn reset_fg_color() {
-        print!("{}", color::Fg(color::Reset))
+    fn reset_fg_color(&self) {
+        self.frame.borrow_mut().set_fg(None);
+    }
+    /// 设置文字属性
+    fn set_attributes(&self, attributes: Attributes) {
+        self.frame.borrow_mut().set_attributes(attributes);
+    }
+}
+
+/// 生成把终端画面从previous更新为frame的输出
+/// 逐个比较单元格(字位, 颜色以及属性), 只输出变化的单元格, 连续的单元格之间不需要移动光标, 颜色和属性相同时不重复设置
+/// previous为None(或大小不同)时先清屏, 然后输出所有非空白的单元格
+fn render(frame: &Frame, previous: Option<&Frame>) -> String {
+    let previous = previous.filter(|previous| {
+        previous.width() == frame.width() && previous.height() == frame.height()
+    });
+    let mut output = String::new();
+    output.push_str(termion::cursor::Hide.as_ref());
+    if previous.is_none() {
+        output.push_str(&format!(
+            "{}{}{}{}",
+            style::Reset,
+            color::Fg(color::Reset),
+            color::Bg(color::Reset),
+            termion::clear::All
+        ));
+    }
+
+    // 终端当前的光标位置, 颜色和属性, None表示未知(需要重新设置)
+    let mut cursor = None;
+    let mut colors = None;
+    let mut attributes = None;
+    for y in 0..frame.height() {
+        for x in 0..frame.width() {
+            let Some(cell) = frame.cell(x, y) else {
+                continue;
+            };
+            let changed = match previous {
+                Some(previous) => previous.cell(x, y) != Some(cell),
+                None => !cell.is_blank(),
+            };
+            // 宽字符的右半部分随左半部分一起输出
+            if !changed || cell.is_continuation() {
+                continue;
+            }
+            if cursor != Some((x, y)) {
+                output.push_str(&termion::cursor::Goto(x as u16 + 1, y as u16 + 1).to_string());
+            }
+            // 终端只能一起关闭所有属性(同时重置颜色), 因此属性变化时先重置, 再设置需要的属性和颜色
+            if attributes != Some(cell.attributes) {
+                output.push_str(&style_of(cell.attributes));
+                attributes = Some(cell.attributes);
+                colors = None;
+            }
+            if colors != Some((cell.fg, cell.bg)) {
+                match cell.fg {
+                    Some(fg) => output.push_str(&color::Fg(fg).to_string()),
+                    None => output.push_str(&color::Fg(color::Reset).to_string()),
+                }
+                match cell.bg {
+                    Some(bg) => output.push_str(&color::Bg(bg).to_string()),
+                    None => output.push_str(&color::Bg(color::Reset).to_string()),
+                }
+                colors = Some((cell.fg, cell.bg));
+            }
+            output.push_str(&cell.grapheme);
+            let width = 1
+                + (x + 1..frame.width())
+                    .take_while(|x| frame.cell(*x, y).is_some_and(|cell| cell.is_continuation()))
+                    .count();
+            cursor = Some((x + width, y));
+        }
+    }
+
+    let (x, y) = frame.cursor();
+    output.push_str(&format!(
+        "{}{}{}{}",
+        style::Reset,
+        color::Fg(color::Reset),
+        color::Bg(color::Reset),
+        termion::cursor::Goto(x as u16 + 1, y as u16 + 1)
+    ));
+    if frame.is_cursor_visible() {
+        output.push_str(termion::cursor::Show.as_ref());
+    }
+    output
+}
+
+/// 重置文字属性后设置attributes中的属性的转义序列
+fn style_of(attributes: Attributes) -> String {
+    let mut output = style::Reset.to_string();
+    if attributes.bold {
+        output.push_str(style::Bold.as_ref());
+    }
+    if attributes.underline {
+        output.push_str(style::Underline.as_ref());
+    }
+    if attributes.reverse {
+        output.push_str(style::Invert.as_ref());
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attribute_changes_are_rendered() {
+        let mut previous = Frame::new(10, 2);
+        previous.draw_text("abc");
+        let mut frame = Frame::new(10, 2);
+        frame.draw_text("a");
+        frame.set_attributes(Attributes {
+            bold: true,
+            ..Attributes::default()
+        });
+        frame.draw_text("b");
+        frame.set_attributes(Attributes::default());
+        frame.draw_text("c");
+
+        // 只有属性变化的单元格也需要输出, 之后的单元格恢复为没有属性
+        let output = render(&frame, Some(&previous));
+        let bold = format!("{}{}", style::Reset, style::Bold);
+        let start = output.find(&bold).expect("bold is set");
+        assert!(output[start..].contains('b'));
+        assert!(!output.contains('a'));
+        assert!(!output.contains('c'));
     }
 }
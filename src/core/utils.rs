@@ -1,5 +1,28 @@
-use std::io::Error;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-pub fn die(error: Error) {
-    panic!("{:?}", error)
+/// 一个字位(制表符除外)在屏幕上占用的列数
+/// 中日韩文字及表情符号占两列, 组合字符等零宽字符占零列; 由多个宽字符组成的字位(如部分表情序列)也只占两列,
+/// 与终端的显示一致. 文本中的列号计算和屏幕绘制都使用这个宽度, 两者才不会错位
+pub fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width().min(2)
+}
+
+/// 字符串的显示宽度(按字位计算)
+pub fn text_width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+/// 按显示宽度截断字符串(String::truncate按字节截断, 遇到多字节字符会panic)
+pub fn truncate_to_width(text: &str, width: usize) -> String {
+    let mut result = String::new();
+    let mut current = 0;
+    for grapheme in text.graphemes(true) {
+        current += grapheme_width(grapheme);
+        if current > width {
+            break;
+        }
+        result.push_str(grapheme);
+    }
+    result
 }
@@ -0,0 +1,772 @@
+use super::{backend::Key, document::Document, editor::Position, row::Row};
+use std::cell::RefCell;
+
+/// 模态编辑(类似Vim)的模式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// 普通模式: 按键为移动光标或编辑命令
+    #[default]
+    Normal,
+    /// 插入模式: 按键插入到文档中, Esc回到普通模式
+    Insert,
+    /// 可视模式: 移动光标扩展选区, 之后对选区执行命令
+    Visual,
+    /// 命令行模式: 在消息栏中输入 `:w`, `:q` 等命令
+    CommandLine,
+}
+
+impl Mode {
+    /// 状态栏中显示的模式名称
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Visual => "VISUAL",
+            Mode::CommandLine => "COMMAND",
+        }
+    }
+}
+
+/// 移动光标的动作, 也可以作为操作符的范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    /// h
+    Left,
+    /// l
+    Right,
+    /// k
+    Up,
+    /// j
+    Down,
+    /// w: 下一个单词的开头
+    WordForward,
+    /// b: 上一个单词的开头
+    WordBackward,
+    /// e: 单词的结尾
+    WordEnd,
+    /// 0: 行首
+    LineStart,
+    /// $: 行尾
+    LineEnd,
+    /// gg: 第一行(有计数时为指定行)
+    FirstLine,
+    /// G: 最后一行(有计数时为指定行)
+    LastLine,
+}
+
+impl Motion {
+    /// 作为操作符的范围时是否按整行操作(dj删除两行)
+    pub fn is_linewise(self) -> bool {
+        matches!(
+            self,
+            Motion::Up | Motion::Down | Motion::FirstLine | Motion::LastLine
+        )
+    }
+
+    /// 作为操作符的范围时是否包含目标位置的字符(de删除到单词最后一个字符)
+    pub fn is_inclusive(self) -> bool {
+        matches!(self, Motion::WordEnd | Motion::LineEnd)
+    }
+}
+
+/// 操作符, 作用于之后的动作所跨越的文本
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// d: 删除(同时复制到剪贴板)
+    Delete,
+    /// c: 删除并进入插入模式
+    Change,
+    /// y: 复制到剪贴板
+    Yank,
+}
+
+/// 进入插入模式的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertAt {
+    /// i: 光标处
+    Cursor,
+    /// a: 光标之后
+    After,
+    /// I: 行首第一个非空白字符
+    LineStart,
+    /// A: 行尾
+    LineEnd,
+    /// o: 在下方新建一行
+    LineBelow,
+    /// O: 在上方新建一行
+    LineAbove,
+}
+
+/// 普通模式的命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalCommand {
+    /// 移动光标
+    Move(Motion),
+    /// 操作符及其范围, 范围为None时作用于整行(dd, cc, yy)
+    Operate(Operator, Option<Motion>),
+    /// 可视模式中对选区执行操作符, 记录选区跨越的行数以及最后一行的列数(只有一行时为字符数)
+    /// 用.重复时作用于光标处同样大小的范围
+    OperateSelection {
+        operator: Operator,
+        lines: usize,
+        columns: usize,
+    },
+    /// x: 删除光标处的字符
+    DeleteChar,
+    /// p/P: 在光标之后/之前粘贴
+    Put { before: bool },
+    /// 进入插入模式
+    Insert(InsertAt),
+    /// v: 进入可视模式
+    Visual,
+    /// u
+    Undo,
+    /// Ctrl-R
+    Redo,
+    /// .: 重复上一次修改
+    Repeat,
+    /// :: 进入命令行模式
+    CommandLine,
+}
+
+impl NormalCommand {
+    /// 是否修改文档, 修改文档的命令可以用.重复
+    pub fn is_change(self) -> bool {
+        match self {
+            NormalCommand::Operate(operator, _)
+            | NormalCommand::OperateSelection { operator, .. } => operator != Operator::Yank,
+            NormalCommand::DeleteChar | NormalCommand::Put { .. } | NormalCommand::Insert(_) => {
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 解析普通模式下输入的按键的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parse {
+    /// 完整的命令及计数(没有输入计数时为None)
+    Complete(Option<usize>, NormalCommand),
+    /// 命令还没有输入完整(如只输入了d或2)
+    Pending,
+    /// 不是有效的命令
+    Invalid,
+}
+
+/// 可以用.重复的修改: 命令以及之后在插入模式中输入的按键
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub count: Option<usize>,
+    pub command: NormalCommand,
+    pub inserted: Vec<Key>,
+}
+
+/// 模态编辑的状态
+#[derive(Debug, Default)]
+pub struct Vim {
+    mode: RefCell<Mode>,
+    pending: RefCell<Vec<Key>>, // 普通模式下已经输入但还不是完整命令的按键
+    linewise: RefCell<bool>,    // 剪贴板中的文本是否为整行(yy, dd复制的文本)
+    last_change: RefCell<Option<Change>>, // 上一次修改, 用于.重复
+    recording: RefCell<Option<Change>>, // 正在插入模式中记录的修改, 回到普通模式时成为上一次修改
+}
+
+impl Vim {
+    /// 获取当前模式
+    pub fn mode(&self) -> Mode {
+        *self.mode.borrow()
+    }
+    /// 设置当前模式
+    pub fn set_mode(&self, mode: Mode) {
+        *self.mode.borrow_mut() = mode
+    }
+    /// 获取剪贴板中的文本是否为整行
+    pub fn is_linewise(&self) -> bool {
+        *self.linewise.borrow()
+    }
+    /// 设置剪贴板中的文本是否为整行
+    pub fn set_linewise(&self, value: bool) {
+        *self.linewise.borrow_mut() = value
+    }
+    /// 获取上一次修改
+    pub fn last_change(&self) -> Option<Change> {
+        self.last_change.borrow().clone()
+    }
+
+    /// 普通模式下输入一个按键, 命令完整或无效时清空已经输入的按键
+    pub fn push_key(&self, key: Key) -> Parse {
+        let mut pending = self.pending.borrow_mut();
+        pending.push(key);
+        let parse = parse(&pending);
+        if parse != Parse::Pending {
+            pending.clear();
+        }
+        parse
+    }
+
+    /// 清空已经输入的按键(离开普通模式时)
+    pub fn clear_pending(&self) {
+        self.pending.borrow_mut().clear()
+    }
+
+    /// 记录一次修改, 进入插入模式的修改在回到普通模式时才完成
+    pub fn record(&self, count: Option<usize>, command: NormalCommand) {
+        let change = Change {
+            count,
+            command,
+            inserted: Vec::new(),
+        };
+        if self.mode() == Mode::Insert {
+            *self.recording.borrow_mut() = Some(change);
+        } else {
+            *self.last_change.borrow_mut() = Some(change);
+        }
+    }
+
+    /// 记录插入模式中输入的按键
+    pub fn record_insert(&self, key: Key) {
+        if let Some(change) = self.recording.borrow_mut().as_mut() {
+            change.inserted.push(key);
+        }
+    }
+
+    /// 回到普通模式, 完成插入模式中记录的修改
+    pub fn finish_insert(&self) {
+        if let Some(change) = self.recording.borrow_mut().take() {
+            *self.last_change.borrow_mut() = Some(change);
+        }
+        self.set_mode(Mode::Normal);
+    }
+}
+
+/// 解析普通模式下输入的按键: [计数] 命令, 或 [计数] 操作符 [计数] 动作
+pub fn parse(keys: &[Key]) -> Parse {
+    let (count, keys) = take_count(keys);
+    let Some(key) = keys.first() else {
+        return Parse::Pending;
+    };
+    let command = match key {
+        Key::Char(c @ ('d' | 'c' | 'y')) => {
+            let operator = match c {
+                'd' => Operator::Delete,
+                'c' => Operator::Change,
+                _ => Operator::Yank,
+            };
+            let rest = &keys[1..];
+            if rest.first() == Some(key) && rest.len() == 1 {
+                return Parse::Complete(count, NormalCommand::Operate(operator, None));
+            }
+            let (motion_count, rest) = take_count(rest);
+            return match parse_motion(rest) {
+                Parse::Complete(_, NormalCommand::Move(motion)) => Parse::Complete(
+                    multiply(count, motion_count),
+                    NormalCommand::Operate(operator, Some(motion)),
+                ),
+                Parse::Pending => Parse::Pending,
+                _ => Parse::Invalid,
+            };
+        }
+        Key::Char('D') => NormalCommand::Operate(Operator::Delete, Some(Motion::LineEnd)),
+        Key::Char('C') => NormalCommand::Operate(Operator::Change, Some(Motion::LineEnd)),
+        Key::Char('x') | Key::Delete => NormalCommand::DeleteChar,
+        Key::Char('p') => NormalCommand::Put { before: false },
+        Key::Char('P') => NormalCommand::Put { before: true },
+        Key::Char('i') | Key::Insert => NormalCommand::Insert(InsertAt::Cursor),
+        Key::Char('a') => NormalCommand::Insert(InsertAt::After),
+        Key::Char('I') => NormalCommand::Insert(InsertAt::LineStart),
+        Key::Char('A') => NormalCommand::Insert(InsertAt::LineEnd),
+        Key::Char('o') => NormalCommand::Insert(InsertAt::LineBelow),
+        Key::Char('O') => NormalCommand::Insert(InsertAt::LineAbove),
+        Key::Char('v') => NormalCommand::Visual,
+        Key::Char('u') => NormalCommand::Undo,
+        Key::Ctrl('r') => NormalCommand::Redo,
+        Key::Char('.') => NormalCommand::Repeat,
+        Key::Char(':') => NormalCommand::CommandLine,
+        _ => {
+            return match parse_motion(keys) {
+                Parse::Complete(_, command) => Parse::Complete(count, command),
+                parse => parse,
+            }
+        }
+    };
+    if keys.len() == 1 {
+        Parse::Complete(count, command)
+    } else {
+        Parse::Invalid
+    }
+}
+
+/// 解析动作, 结果中的计数总是None
+fn parse_motion(keys: &[Key]) -> Parse {
+    let motion = match keys {
+        [] | [Key::Char('g')] => return Parse::Pending,
+        [Key::Char('h') | Key::Left | Key::Backspace] => Motion::Left,
+        [Key::Char('l') | Key::Right | Key::Char(' ')] => Motion::Right,
+        [Key::Char('k') | Key::Up] => Motion::Up,
+        [Key::Char('j') | Key::Down] => Motion::Down,
+        [Key::Char('w')] => Motion::WordForward,
+        [Key::Char('b')] => Motion::WordBackward,
+        [Key::Char('e')] => Motion::WordEnd,
+        [Key::Char('0') | Key::Home] => Motion::LineStart,
+        [Key::Char('$') | Key::End] => Motion::LineEnd,
+        [Key::Char('g'), Key::Char('g')] => Motion::FirstLine,
+        [Key::Char('G')] => Motion::LastLine,
+        _ => return Parse::Invalid,
+    };
+    Parse::Complete(None, NormalCommand::Move(motion))
+}
+
+/// 读取按键开头的计数, 开头的0是移动到行首而不是计数
+fn take_count(keys: &[Key]) -> (Option<usize>, &[Key]) {
+    let digits = keys
+        .iter()
+        .enumerate()
+        .take_while(|(index, key)| match key {
+            Key::Char('0') => *index > 0,
+            Key::Char(c) => c.is_ascii_digit(),
+            _ => false,
+        })
+        .count();
+    if digits == 0 {
+        return (None, keys);
+    }
+    let count = keys[..digits].iter().fold(0usize, |count, key| match key {
+        Key::Char(c) => count
+            .saturating_mul(10)
+            .saturating_add(c.to_digit(10).unwrap_or(0) as usize),
+        _ => count,
+    });
+    (Some(count.max(1)), &keys[digits..])
+}
+
+/// 操作符和动作都有计数时相乘(2d3w删除6个单词)
+fn multiply(first: Option<usize>, second: Option<usize>) -> Option<usize> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(first.saturating_mul(second)),
+        (first, second) => first.or(second),
+    }
+}
+
+/// 从from开始执行count次动作后的位置
+/// 上下移动时不改变x, 由调用者按显示列调整
+pub fn target(
+    document: &Document,
+    from: Position,
+    motion: Motion,
+    count: Option<usize>,
+) -> Position {
+    let times = count.unwrap_or(1);
+    let last = document.len().saturating_sub(1);
+    let row_len = |y: usize| document.row(y).map_or(0, Row::len);
+    let repeat = |step: fn(&Document, Position) -> Position| {
+        (0..times).fold(from, |position, _| step(document, position))
+    };
+    match motion {
+        Motion::Left => Position {
+            x: from.x.saturating_sub(times),
+            y: from.y,
+        },
+        Motion::Right => Position {
+            x: from.x.saturating_add(times).min(row_len(from.y)),
+            y: from.y,
+        },
+        Motion::Up => Position {
+            x: from.x,
+            y: from.y.saturating_sub(times),
+        },
+        Motion::Down => Position {
+            x: from.x,
+            y: from.y.saturating_add(times).min(last),
+        },
+        Motion::WordForward => repeat(word_forward),
+        Motion::WordBackward => repeat(word_backward),
+        Motion::WordEnd => repeat(word_end),
+        Motion::LineStart => Position { x: 0, y: from.y },
+        Motion::LineEnd => {
+            let y = from.y.saturating_add(times - 1).min(last);
+            Position {
+                x: row_len(y).saturating_sub(1),
+                y,
+            }
+        }
+        Motion::FirstLine | Motion::LastLine => {
+            let default = if motion == Motion::FirstLine { 0 } else { last };
+            let y = count.map_or(default, |line| (line - 1).min(last));
+            Position {
+                x: first_non_blank(document, y),
+                y,
+            }
+        }
+    }
+}
+
+/// 操作符作用的范围
+#[derive(Debug, Clone, Copy)]
+pub enum Range {
+    /// 起点到终点(不包含终点)之间的文本
+    Chars(Position, Position),
+    /// 两行之间(包含两行)的整行
+    Lines(usize, usize),
+}
+
+/// 从from开始的操作符的范围, motion为None时作用于count行(dd, cc, yy)
+pub fn range(
+    document: &Document,
+    from: Position,
+    operator: Operator,
+    motion: Option<Motion>,
+    count: Option<usize>,
+) -> Range {
+    let last = document.len().saturating_sub(1);
+    let Some(motion) = motion else {
+        let end = from.y.saturating_add(count.unwrap_or(1) - 1).min(last);
+        return Range::Lines(from.y, end);
+    };
+    if motion.is_linewise() {
+        let target = target(document, from, motion, count);
+        return Range::Lines(from.y.min(target.y), from.y.max(target.y));
+    }
+    // cw与ce相同, 不修改单词之后的空白. 从前一个位置开始, 光标在单词结尾时只修改到该单词结尾
+    let change_word = operator == Operator::Change
+        && motion == Motion::WordForward
+        && class_at(document, from) != Class::Blank;
+    let mut end = if change_word {
+        let start = previous(document, from).unwrap_or(from);
+        (0..count.unwrap_or(1)).fold(start, |position, _| word_end(document, position))
+    } else {
+        target(document, from, motion, count)
+    };
+    // dw跨过的最后一个单词在行尾时, 只作用到该单词所在行的行尾, 不包括下一行开头的空白
+    if motion == Motion::WordForward && !change_word {
+        let last_word = target(document, from, motion, Some(count.unwrap_or(1) - 1));
+        if end.y > last_word.y {
+            end = Position {
+                x: document.row(last_word.y).map_or(0, Row::len),
+                y: last_word.y,
+            };
+        }
+    }
+    if motion.is_inclusive() || change_word {
+        end.x = end
+            .x
+            .saturating_add(1)
+            .min(document.row(end.y).map_or(0, Row::len));
+    }
+    if (end.y, end.x) < (from.y, from.x) {
+        Range::Chars(end, from)
+    } else {
+        Range::Chars(from, end)
+    }
+}
+
+/// 从from开始, 与可视模式选区同样大小的范围(见NormalCommand::OperateSelection)
+pub fn selection_range(document: &Document, from: Position, lines: usize, columns: usize) -> Range {
+    let y = from
+        .y
+        .saturating_add(lines)
+        .min(document.len().saturating_sub(1));
+    let x = if y == from.y {
+        from.x.saturating_add(columns)
+    } else {
+        columns
+    };
+    let end = Position {
+        x: x.min(document.row(y).map_or(0, Row::len)),
+        y,
+    };
+    Range::Chars(from, end)
+}
+
+/// 行中第一个非空白字符的位置
+pub fn first_non_blank(document: &Document, y: usize) -> usize {
+    document.row(y).map_or(0, |row| {
+        (0..row.len())
+            .find(|&x| class_at(document, Position { x, y }) != Class::Blank)
+            .unwrap_or(0)
+    })
+}
+
+/// 字符类别, 单词由同一类别的连续字符组成
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+/// 指定位置的字符类别, 行尾(换行)视为空白
+fn class_at(document: &Document, position: Position) -> Class {
+    let Some(row) = document.row(position.y) else {
+        return Class::Blank;
+    };
+    if position.x >= row.len() {
+        return Class::Blank;
+    }
+    match row.slice(position.x, 1).chars().next() {
+        Some(c) if c.is_whitespace() => Class::Blank,
+        Some(c) if c.is_alphanumeric() || c == '_' => Class::Word,
+        Some(_) => Class::Punctuation,
+        None => Class::Blank,
+    }
+}
+
+/// 空行也作为一个单词
+fn is_empty_line(document: &Document, position: Position) -> bool {
+    position.x == 0 && document.row(position.y).is_some_and(|row| row.len() == 0)
+}
+
+/// 下一个位置, 行尾之后是下一行的行首, 到达文档末尾时返回None
+fn next(document: &Document, position: Position) -> Option<Position> {
+    let row = document.row(position.y)?;
+    if position.x < row.len() {
+        Some(Position {
+            x: position.x + 1,
+            y: position.y,
+        })
+    } else if position.y + 1 < document.len() {
+        Some(Position {
+            x: 0,
+            y: position.y + 1,
+        })
+    } else {
+        None
+    }
+}
+
+/// 上一个位置, 行首之前是上一行的行尾, 到达文档开头时返回None
+fn previous(document: &Document, position: Position) -> Option<Position> {
+    if position.x > 0 {
+        Some(Position {
+            x: position.x - 1,
+            y: position.y,
+        })
+    } else if position.y > 0 {
+        let y = position.y - 1;
+        Some(Position {
+            x: document.row(y).map_or(0, Row::len),
+            y,
+        })
+    } else {
+        None
+    }
+}
+
+/// w: 跳过当前单词及之后的空白
+fn word_forward(document: &Document, from: Position) -> Position {
+    let class = class_at(document, from);
+    let Some(mut position) = next(document, from) else {
+        return from;
+    };
+    while class != Class::Blank && class_at(document, position) == class {
+        match next(document, position) {
+            Some(next) => position = next,
+            None => return position,
+        }
+    }
+    while class_at(document, position) == Class::Blank && !is_empty_line(document, position) {
+        match next(document, position) {
+            Some(next) => position = next,
+            None => return position,
+        }
+    }
+    position
+}
+
+/// b: 跳过之前的空白, 移动到单词开头
+fn word_backward(document: &Document, from: Position) -> Position {
+    let Some(mut position) = previous(document, from) else {
+        return from;
+    };
+    while class_at(document, position) == Class::Blank && !is_empty_line(document, position) {
+        match previous(document, position) {
+            Some(previous) => position = previous,
+            None => return position,
+        }
+    }
+    let class = class_at(document, position);
+    if class == Class::Blank {
+        return position;
+    }
+    while let Some(previous) = previous(document, position) {
+        if class_at(document, previous) != class {
+            break;
+        }
+        position = previous;
+    }
+    position
+}
+
+/// e: 跳过之后的空白, 移动到单词结尾
+fn word_end(document: &Document, from: Position) -> Position {
+    let Some(mut position) = next(document, from) else {
+        return from;
+    };
+    while class_at(document, position) == Class::Blank {
+        match next(document, position) {
+            Some(next) => position = next,
+            None => return position,
+        }
+    }
+    let class = class_at(document, position);
+    while let Some(next) = next(document, position) {
+        if class_at(document, next) != class {
+            break;
+        }
+        position = next;
+    }
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::filetype::FileType;
+
+    fn keys(text: &str) -> Vec<Key> {
+        text.chars().map(Key::Char).collect()
+    }
+
+    fn document(lines: &[&str]) -> Document {
+        let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        Document::scratch(&lines, FileType::default())
+    }
+
+    fn at(x: usize, y: usize) -> Position {
+        Position { x, y }
+    }
+
+    /// 范围的起点和终点, 以(x, y)表示
+    fn chars(range: Range) -> ((usize, usize), (usize, usize)) {
+        match range {
+            Range::Chars(start, end) => ((start.x, start.y), (end.x, end.y)),
+            Range::Lines(..) => panic!("expected a character range, got {:?}", range),
+        }
+    }
+
+    #[test]
+    fn take_count_reads_leading_digits() {
+        assert_eq!(take_count(&keys("12dw")), (Some(12), &keys("dw")[..]));
+        assert_eq!(take_count(&keys("dw")), (None, &keys("dw")[..]));
+        // 开头的0是动作, 之后的0属于计数
+        assert_eq!(take_count(&keys("0")), (None, &keys("0")[..]));
+        assert_eq!(take_count(&keys("10j")), (Some(10), &keys("j")[..]));
+    }
+
+    #[test]
+    fn parse_commands() {
+        use NormalCommand::*;
+        assert_eq!(
+            parse(&keys("cw")),
+            Parse::Complete(None, Operate(Operator::Change, Some(Motion::WordForward)))
+        );
+        assert_eq!(
+            parse(&keys("2d3w")),
+            Parse::Complete(
+                Some(6),
+                Operate(Operator::Delete, Some(Motion::WordForward))
+            )
+        );
+        assert_eq!(
+            parse(&keys("3dd")),
+            Parse::Complete(Some(3), Operate(Operator::Delete, None))
+        );
+        assert_eq!(parse(&keys("d")), Parse::Pending);
+        assert_eq!(parse(&keys("d2")), Parse::Pending);
+        assert_eq!(parse(&keys("dg")), Parse::Pending);
+        assert_eq!(
+            parse(&keys("dgg")),
+            Parse::Complete(None, Operate(Operator::Delete, Some(Motion::FirstLine)))
+        );
+        assert_eq!(parse(&keys("dz")), Parse::Invalid);
+        assert_eq!(
+            parse(&keys("5G")),
+            Parse::Complete(Some(5), Move(Motion::LastLine))
+        );
+        assert_eq!(
+            parse(&keys("0")),
+            Parse::Complete(None, Move(Motion::LineStart))
+        );
+    }
+
+    #[test]
+    fn word_targets() {
+        let document = document(&["foo bar.baz", "", "  qux"]);
+        let target = |from, motion, count| {
+            let position = target(&document, from, motion, count);
+            (position.x, position.y)
+        };
+        assert_eq!(target(at(0, 0), Motion::WordForward, None), (4, 0));
+        assert_eq!(target(at(4, 0), Motion::WordForward, None), (7, 0));
+        // 空行也是一个单词
+        assert_eq!(target(at(8, 0), Motion::WordForward, None), (0, 1));
+        assert_eq!(target(at(0, 0), Motion::WordForward, Some(5)), (2, 2));
+        assert_eq!(target(at(2, 2), Motion::WordBackward, None), (0, 1));
+        assert_eq!(target(at(0, 0), Motion::WordEnd, None), (2, 0));
+        assert_eq!(target(at(0, 0), Motion::LineEnd, None), (10, 0));
+        assert_eq!(target(at(5, 0), Motion::LastLine, None), (2, 2));
+        assert_eq!(target(at(5, 2), Motion::FirstLine, Some(1)), (0, 0));
+    }
+
+    #[test]
+    fn operator_ranges() {
+        let document = document(&["foo bar baz", "qux quux", "end"]);
+        let range =
+            |from, operator, motion, count| chars(range(&document, from, operator, motion, count));
+        // cw不包含单词之后的空白
+        assert_eq!(
+            range(at(0, 0), Operator::Change, Some(Motion::WordForward), None),
+            ((0, 0), (3, 0))
+        );
+        assert_eq!(
+            range(at(0, 0), Operator::Delete, Some(Motion::WordForward), None),
+            ((0, 0), (4, 0))
+        );
+        // 最后一个单词的dw只删除到行尾
+        assert_eq!(
+            range(at(8, 0), Operator::Delete, Some(Motion::WordForward), None),
+            ((8, 0), (11, 0))
+        );
+        // 跨行时作用到上一个单词所在行的行尾
+        assert_eq!(
+            range(
+                at(4, 0),
+                Operator::Delete,
+                Some(Motion::WordForward),
+                Some(4)
+            ),
+            ((4, 0), (8, 1))
+        );
+        // 2d3w: 计数相乘
+        let count = match parse(&keys("2d3w")) {
+            Parse::Complete(count, _) => count,
+            parse => panic!("unexpected {:?}", parse),
+        };
+        assert_eq!(
+            range(at(0, 0), Operator::Delete, Some(Motion::WordForward), count),
+            ((0, 0), (3, 2))
+        );
+        assert_eq!(
+            range(at(4, 0), Operator::Delete, Some(Motion::WordBackward), None),
+            ((0, 0), (4, 0))
+        );
+        assert_eq!(
+            range(at(4, 0), Operator::Delete, Some(Motion::LineEnd), None),
+            ((4, 0), (11, 0))
+        );
+        match super::range(
+            &document,
+            at(1, 1),
+            Operator::Delete,
+            Some(Motion::Down),
+            None,
+        ) {
+            Range::Lines(first, last) => assert_eq!((first, last), (1, 2)),
+            range => panic!("expected a line range, got {:?}", range),
+        }
+        match super::range(&document, at(1, 1), Operator::Yank, None, Some(5)) {
+            Range::Lines(first, last) => assert_eq!((first, last), (1, 2)),
+            range => panic!("expected a line range, got {:?}", range),
+        }
+    }
+}
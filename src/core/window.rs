@@ -0,0 +1,212 @@
+use super::editor::Position;
+
+/// 终端中的矩形区域(单位为列和行)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    /// 是否包含指定的屏幕位置
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+}
+
+/// 分割方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    /// 上下排列
+    Horizontal,
+    /// 左右排列
+    Vertical,
+}
+
+/// 窗口(窗格), 显示某个缓冲区的一部分
+/// 当前窗口的光标位置和偏移量由Editor直接持有, 切换窗口时与这里保存的状态交换
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Window {
+    buffer: usize, // 显示的缓冲区下标
+    cursor_position: Position,
+    offset: Position,
+}
+
+impl Window {
+    pub fn new(buffer: usize, cursor_position: Position, offset: Position) -> Self {
+        Self {
+            buffer,
+            cursor_position,
+            offset,
+        }
+    }
+    /// 显示的缓冲区下标
+    pub fn buffer(&self) -> usize {
+        self.buffer
+    }
+    /// 光标位置
+    pub fn cursor_position(&self) -> Position {
+        self.cursor_position
+    }
+    /// 偏移量
+    pub fn offset(&self) -> Position {
+        self.offset
+    }
+}
+
+/// 布局计算的结果
+#[derive(Debug, Default)]
+pub struct Arrangement {
+    /// 按下标排列的窗口及其区域
+    pub windows: Vec<(usize, Rect)>,
+    /// 窗口之间的分隔线
+    pub separators: Vec<(Split, Rect)>,
+}
+
+/// 窗口布局, 每次分割都把一个窗口分为两个, 同方向的连续分割合并为同一层
+#[derive(Debug)]
+pub enum Layout {
+    /// 窗口下标
+    Window(usize),
+    Split(Split, Vec<Layout>),
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::Window(0)
+    }
+}
+
+impl Layout {
+    /// 计算每个窗口的区域, 以及窗口之间分隔线的区域
+    /// 同一层的窗口平分空间, 相邻窗口之间留出一行(上下排列)或一列(左右排列)作为分隔线
+    pub fn arrange(&self, rect: Rect) -> Arrangement {
+        let mut arrangement = Arrangement::default();
+        self.arrange_into(rect, &mut arrangement);
+        arrangement.windows.sort_by_key(|(index, _)| *index);
+        arrangement
+    }
+
+    fn arrange_into(&self, rect: Rect, arrangement: &mut Arrangement) {
+        let (direction, children) = match self {
+            Layout::Window(index) => {
+                arrangement.windows.push((*index, rect));
+                return;
+            }
+            Layout::Split(direction, children) => (*direction, children),
+        };
+        let total = match direction {
+            Split::Horizontal => rect.height,
+            Split::Vertical => rect.width,
+        };
+        let count = children.len();
+        let available = total.saturating_sub(count - 1);
+        let mut start = 0;
+        for (index, child) in children.iter().enumerate() {
+            let size = available / count + usize::from(index < available % count);
+            let child_rect = match direction {
+                Split::Horizontal => Rect {
+                    y: rect.y + start,
+                    height: size,
+                    ..rect
+                },
+                Split::Vertical => Rect {
+                    x: rect.x + start,
+                    width: size,
+                    ..rect
+                },
+            };
+            child.arrange_into(child_rect, arrangement);
+            start += size;
+            if index + 1 < count {
+                let separator = match direction {
+                    Split::Horizontal => Rect {
+                        y: rect.y + start,
+                        height: 1,
+                        ..rect
+                    },
+                    Split::Vertical => Rect {
+                        x: rect.x + start,
+                        width: 1,
+                        ..rect
+                    },
+                };
+                arrangement.separators.push((direction, separator));
+                start += 1;
+            }
+        }
+    }
+
+    /// 分割窗口, 新窗口放在原窗口之后(下方或右侧)
+    pub fn split(&mut self, window: usize, new_window: usize, direction: Split) -> bool {
+        match self {
+            Layout::Window(index) if *index == window => {
+                *self = Layout::Split(
+                    direction,
+                    vec![Layout::Window(window), Layout::Window(new_window)],
+                );
+                true
+            }
+            Layout::Window(_) => false,
+            Layout::Split(split, children) => {
+                // 与所在层方向相同时直接插入到同一层
+                if *split == direction {
+                    if let Some(position) = children.iter().position(
+                        |child| matches!(child, Layout::Window(index) if *index == window),
+                    ) {
+                        children.insert(position + 1, Layout::Window(new_window));
+                        return true;
+                    }
+                }
+                children
+                    .iter_mut()
+                    .any(|child| child.split(window, new_window, direction))
+            }
+        }
+    }
+
+    /// 删除窗口, 之后的窗口下标减一
+    /// 某一层只剩一个窗口时, 该层与上一层合并
+    pub fn remove(&mut self, window: usize) -> bool {
+        let removed = self.remove_window(window);
+        if removed {
+            self.renumber(window);
+        }
+        removed
+    }
+
+    fn remove_window(&mut self, window: usize) -> bool {
+        let Layout::Split(_, children) = self else {
+            return false;
+        };
+        let removed = if let Some(position) = children
+            .iter()
+            .position(|child| matches!(child, Layout::Window(index) if *index == window))
+        {
+            children.remove(position);
+            true
+        } else {
+            children.iter_mut().any(|child| child.remove_window(window))
+        };
+        if children.len() == 1 {
+            if let Some(child) = children.pop() {
+                *self = child;
+            }
+        }
+        removed
+    }
+
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Layout::Window(index) if *index > removed => *index -= 1,
+            Layout::Window(_) => (),
+            Layout::Split(_, children) => {
+                for child in children {
+                    child.renumber(removed);
+                }
+            }
+        }
+    }
+}
@@ -2,4 +2,13 @@
 pub mod core;
 
 /// core mod defined after export
-pub use core::editor::Editor;
+pub use core::{
+    backend::{
+        Attributes, Event, InputSource, Key, MouseButton, MouseEvent, RenderTarget, Rgb, Size,
+    },
+    config::{Colors, Config, FileSettings, LineEnding},
+    editor::{Editor, Position},
+    error::Error,
+    headless::{Cell, MemoryScreen, ScriptedInput},
+    keymap::{Command, Keymap, Preset},
+};
@@ -1,5 +1,13 @@
 use lecto::Editor;
+use std::process::ExitCode;
 
-fn main() {
-    Editor::default().run()
+fn main() -> ExitCode {
+    // 编辑器(以及其中的终端)在输出错误信息之前已经被释放, 终端已经恢复
+    match Editor::new().and_then(|mut editor| editor.run()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("lecto: {}", err);
+            ExitCode::FAILURE
+        }
+    }
 }
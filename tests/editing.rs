@@ -0,0 +1,510 @@
+//! 通过ScriptedInput和MemoryScreen在内存中运行完整的编辑会话
+use lecto::{Config, Editor, Error, Event, Key, MemoryScreen, ScriptedInput};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process, thread,
+    time::Duration,
+};
+
+/// 为每个测试创建单独的临时目录
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("lecto-test-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// 在临时目录中创建文件, 返回文件路径
+fn create_file(dir: &Path, name: &str, contents: &str) -> String {
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+/// 输入文本(每个字符一个按键)
+fn text(text: &str) -> Vec<Event> {
+    text.chars().map(|c| Event::Key(Key::Char(c))).collect()
+}
+
+fn key(key: Key) -> Vec<Event> {
+    vec![Event::Key(key)]
+}
+
+/// 按顺序执行事件, 返回编辑器的运行结果以及最后的画面
+fn run(files: &[&str], events: Vec<Vec<Event>>) -> (Result<(), Error>, MemoryScreen) {
+    let screen = MemoryScreen::new(80, 24);
+    let input = ScriptedInput::new(events.concat());
+    let mut editor = Editor::with_backend(Box::new(input), Box::new(screen.clone()), files);
+    (editor.run(), screen)
+}
+
+/// 与run相同, 但启用模态编辑
+fn run_vim(files: &[&str], events: Vec<Vec<Event>>) -> (Result<(), Error>, MemoryScreen) {
+    let screen = MemoryScreen::new(80, 24);
+    let input = ScriptedInput::new(events.concat());
+    let mut config = Config::default();
+    config.vim = true;
+    let mut editor = Editor::with_config(Box::new(input), Box::new(screen.clone()), files, config);
+    (editor.run(), screen)
+}
+
+#[test]
+fn typing_and_saving() {
+    let dir = temp_dir("typing");
+    let file = create_file(&dir, "a.txt", "hello\nworld\n");
+    let (result, screen) = run(
+        &[&file],
+        vec![
+            text(">> "),
+            key(Key::Down),
+            key(Key::End),
+            text("!\n"),
+            text("new line"),
+            key(Key::Ctrl('s')),
+            key(Key::Ctrl('q')),
+        ],
+    );
+    assert!(result.is_ok());
+    assert!(screen.text().contains("Goodbye."));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        ">> hello\nworld!\nnew line\n"
+    );
+}
+
+#[test]
+fn screen_shows_typed_text_and_status() {
+    let dir = temp_dir("screen");
+    let file = create_file(&dir, "a.txt", "hello\n");
+    let (result, screen) = run(&[&file], vec![text("abc")]);
+    // 输入用完后编辑器结束运行, 保留最后一帧画面
+    assert!(result.is_err());
+    assert_eq!(screen.row_text(0), "abchello");
+    assert!(screen.text().contains("1 lines(modified)"));
+    assert_eq!(screen.cursor(), (3, 0));
+}
+
+#[test]
+fn undo_and_redo() {
+    let dir = temp_dir("undo");
+    let file = create_file(&dir, "a.txt", "one\n");
+    let (result, _) = run(
+        &[&file],
+        vec![
+            key(Key::End),
+            text(" two three"),
+            // 撤销以单词为单位
+            key(Key::Ctrl('z')),
+            key(Key::Ctrl('z')),
+            key(Key::Ctrl('y')),
+            key(Key::Ctrl('s')),
+            key(Key::Ctrl('q')),
+        ],
+    );
+    assert!(result.is_ok());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "one two\n");
+}
+
+#[test]
+fn undo_back_to_saved_state_is_clean() {
+    let dir = temp_dir("undo-clean");
+    let file = create_file(&dir, "a.txt", "one\n");
+    let (result, _) = run(
+        &[&file],
+        vec![text("x"), key(Key::Ctrl('z')), key(Key::Ctrl('q'))],
+    );
+    // 撤销后与保存时一致, 退出时不需要确认
+    assert!(result.is_ok());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "one\n");
+}
+
+#[test]
+fn quit_prompts_on_dirty_buffer() {
+    let dir = temp_dir("quit-prompt");
+    let file = create_file(&dir, "a.txt", "one\n");
+    let (result, screen) = run(&[&file], vec![text("x"), key(Key::Ctrl('q'))]);
+    // 等待确认时输入结束
+    assert!(result.is_err());
+    // 文件名是完整路径, 提示可能被截断
+    assert!(screen.text().contains("Unsaved changes in"));
+}
+
+#[test]
+fn enter_in_empty_buffer_is_undoable_change() {
+    let (result, screen) = run(&[], vec![key(Key::Char('\n')), key(Key::Ctrl('q'))]);
+    assert!(result.is_err());
+    assert!(screen.text().contains("Unsaved changes in"));
+
+    let (result, _) = run(
+        &[],
+        vec![
+            key(Key::Char('\n')),
+            key(Key::Ctrl('z')),
+            key(Key::Ctrl('q')),
+        ],
+    );
+    assert!(result.is_ok());
+}
+
+#[test]
+fn quit_prompt_can_be_declined_or_accepted() {
+    let dir = temp_dir("quit-answer");
+    let file = create_file(&dir, "a.txt", "one\n");
+    let (result, screen) = run(
+        &[&file],
+        vec![
+            text("x"),
+            key(Key::Ctrl('q')),
+            text("n\n"),
+            text("y"),
+            key(Key::Ctrl('q')),
+            text("y\n"),
+        ],
+    );
+    assert!(result.is_ok());
+    assert!(screen.text().contains("Goodbye."));
+    // 没有保存
+    assert_eq!(fs::read_to_string(&file).unwrap(), "one\n");
+}
+
+#[test]
+fn incremental_search_moves_cursor() {
+    let dir = temp_dir("search");
+    let file = create_file(&dir, "a.txt", "alpha\nbeta\ngamma beta\n");
+    let (result, _) = run(
+        &[&file],
+        vec![
+            key(Key::Ctrl('f')),
+            text("beta"),
+            // 跳转到下一个匹配项
+            key(Key::Right),
+            text("\n"),
+            text("X"),
+            key(Key::Ctrl('s')),
+            key(Key::Ctrl('q')),
+        ],
+    );
+    assert!(result.is_ok());
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "alpha\nbeta\ngamma Xbeta\n"
+    );
+}
+
+#[test]
+fn canceled_search_restores_cursor() {
+    let dir = temp_dir("search-cancel");
+    let file = create_file(&dir, "a.txt", "alpha\nbeta\n");
+    let (result, _) = run(
+        &[&file],
+        vec![
+            key(Key::Ctrl('f')),
+            text("beta"),
+            key(Key::Esc),
+            text("X"),
+            key(Key::Ctrl('s')),
+            key(Key::Ctrl('q')),
+        ],
+    );
+    assert!(result.is_ok());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "Xalpha\nbeta\n");
+}
+
+#[test]
+fn replace_all_with_captures() {
+    let dir = temp_dir("replace-all");
+    let file = create_file(&dir, "a.txt", "foo=1\nbar=2\nfoo=3\n");
+    let (result, screen) = run(
+        &[&file],
+        vec![
+            key(Key::Ctrl('r')),
+            text("(\\w+)=(\\d)\n"),
+            text("$2:$1\n"),
+            text("a"),
+            key(Key::Ctrl('s')),
+        ],
+    );
+    assert!(result.is_err());
+    assert!(screen.text().contains("File saved successfully."));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "1:foo\n2:bar\n3:foo\n");
+}
+
+#[test]
+fn replace_inside_graphemes_continues_after_replacement() {
+    let dir = temp_dir("replace-graphemes");
+    // 匹配从组合字符开始, 实际替换的是之后完整的字位; 替换内容本身也能匹配, 不能再次被替换
+    let file = create_file(&dir, "a.txt", "e\u{301}a\n");
+    let (result, _) = run(
+        &[&file],
+        vec![
+            key(Key::Ctrl('r')),
+            text("\u{301}?a\n"),
+            text("aa\n"),
+            text("a"),
+            key(Key::Ctrl('s')),
+        ],
+    );
+    assert!(result.is_err());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "e\u{301}aa\n");
+}
+
+#[test]
+fn replace_confirms_each_match_and_undoes_at_once() {
+    let dir = temp_dir("replace-confirm");
+    let file = create_file(&dir, "a.txt", "cat cat cat\n");
+    let (result, _) = run(
+        &[&file],
+        vec![
+            key(Key::Ctrl('r')),
+            text("cat\n"),
+            text("dog\n"),
+            text("yny"),
+            key(Key::Ctrl('s')),
+        ],
+    );
+    assert!(result.is_err());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "dog cat dog\n");
+
+    let (result, _) = run(
+        &[&file],
+        vec![
+            key(Key::Ctrl('r')),
+            text("dog\n"),
+            text("cow\n"),
+            text("a"),
+            key(Key::Ctrl('z')),
+            key(Key::Ctrl('q')),
+        ],
+    );
+    // 整个替换作为一个撤销步骤, 撤销后文档没有修改
+    assert!(result.is_ok());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "dog cat dog\n");
+}
+
+#[test]
+fn split_windows_share_the_buffer() {
+    let dir = temp_dir("split");
+    let file = create_file(&dir, "a.txt", "shared line\n");
+    let (result, screen) = run(&[&file], vec![key(Key::Alt('s')), text(">")]);
+    assert!(result.is_err());
+    let text = screen.text();
+    // 两个窗口都显示修改后的内容
+    assert_eq!(text.matches(">shared line").count(), 2);
+    assert!(text.contains('─'));
+}
+
+#[test]
+fn split_windows_keep_separate_cursors() {
+    let dir = temp_dir("split-cursor");
+    let file = create_file(&dir, "a.txt", "one\ntwo\nthree\n");
+    let (result, _) = run(
+        &[&file],
+        vec![
+            key(Key::Alt('v')),
+            key(Key::Down),
+            key(Key::Down),
+            text("3"),
+            // 切换到另一个窗口, 光标仍在第一行
+            key(Key::Alt('w')),
+            text("1"),
+            key(Key::Alt('c')),
+            key(Key::Ctrl('s')),
+            key(Key::Ctrl('q')),
+        ],
+    );
+    assert!(result.is_ok());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "1one\ntwo\n3three\n");
+}
+
+/// 创建上次异常退出时留下的交换文件(写入的进程已经不存在)
+fn create_swap(dir: &Path, name: &str, contents: &str) -> PathBuf {
+    let path = dir.join(format!(".{}.lecto-swp", name));
+    fs::write(&path, format!("LECTO-SWAP 99999999\n{}", contents)).unwrap();
+    path
+}
+
+#[test]
+fn swap_file_can_be_recovered() {
+    let dir = temp_dir("swap-recover");
+    let file = create_file(&dir, "a.txt", "saved\n");
+    let swap = create_swap(&dir, "a.txt", "unsaved\n");
+    let (result, _) = run(
+        &[&file],
+        vec![text("r\n"), key(Key::Ctrl('s')), key(Key::Ctrl('q'))],
+    );
+    assert!(result.is_ok());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "unsaved\n");
+    assert!(!swap.exists());
+}
+
+#[test]
+fn escape_keeps_the_swap_file() {
+    let dir = temp_dir("swap-keep");
+    let file = create_file(&dir, "a.txt", "saved\n");
+    let swap = create_swap(&dir, "a.txt", "unsaved\n");
+    let (result, screen) = run(&[&file], vec![key(Key::Esc), text("x")]);
+    assert!(result.is_err());
+    assert_eq!(screen.row_text(0), "xsaved");
+    let (result, _) = run(
+        &[&file],
+        vec![key(Key::Esc), text("x"), key(Key::Ctrl('q')), text("y\n")],
+    );
+    assert!(result.is_ok());
+    // 编辑和退出都不会覆盖或删除保留的交换文件
+    assert_eq!(
+        fs::read_to_string(&swap).unwrap(),
+        "LECTO-SWAP 99999999\nunsaved\n"
+    );
+}
+
+#[test]
+fn vim_x_on_empty_line_keeps_clipboard() {
+    let dir = temp_dir("vim-x-empty");
+    let file = create_file(&dir, "a.txt", "a\n\nb\n");
+    let (result, _) = run_vim(&[&file], vec![text("yyjxp:wq\n")]);
+    assert!(result.is_ok());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "a\n\na\nb\n");
+}
+
+#[test]
+fn vim_repeats_visual_changes() {
+    let dir = temp_dir("vim-visual-repeat");
+    let file = create_file(&dir, "a.txt", "abcdef\nghijkl\nmnopqr\nstuvwx\n");
+    let (result, _) = run_vim(
+        &[&file],
+        vec![
+            text("vlldj0."),
+            text("jvlcX"),
+            key(Key::Esc),
+            text("j0.:wq\n"),
+        ],
+    );
+    assert!(result.is_ok());
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "def\njkl\nXopqr\nXuvwx\n"
+    );
+}
+
+#[test]
+fn open_failure_shows_the_error() {
+    // 使用较短的相对路径, 避免消息被截断
+    let (result, screen) = run(&["lecto-missing/a.txt"], vec![]);
+    assert!(result.is_err());
+    assert!(screen.text().contains("Error opening file"));
+    assert!(screen.text().contains("No such file or directory"));
+}
+
+#[test]
+fn all_open_failures_are_shown() {
+    // 消息较长, 使用较宽的屏幕
+    let screen = MemoryScreen::new(200, 24);
+    let input = ScriptedInput::new(vec![]);
+    let files = ["lecto-missing/a", "lecto-missing/b"];
+    let mut editor = Editor::with_backend(Box::new(input), Box::new(screen.clone()), &files);
+    assert!(editor.run().is_err());
+    assert!(screen.text().contains("2 files were not opened"));
+    assert!(screen.text().contains("lecto-missing/a:"));
+    assert!(screen.text().contains("lecto-missing/b:"));
+}
+
+#[test]
+fn several_files_can_be_opened() {
+    let dir = temp_dir("several-files");
+    let first = create_file(&dir, "a.txt", "first\n");
+    let second = create_file(&dir, "b.txt", "second\n");
+    let (result, screen) = run(&[&first, &second], vec![key(Key::Alt('n'))]);
+    assert!(result.is_err());
+    assert_eq!(screen.row_text(0), "second");
+}
+
+#[test]
+fn external_change_in_background_buffer_is_detected() {
+    let dir = temp_dir("external-change");
+    let first = create_file(&dir, "a.txt", "first\n");
+    let second = create_file(&dir, "b.txt", "second\n");
+    let screen = MemoryScreen::new(80, 24);
+    let input = ScriptedInput::new([vec![Event::Tick], text("r\n")].concat());
+    let mut editor = Editor::with_backend(
+        Box::new(input),
+        Box::new(screen.clone()),
+        &[&first, &second],
+    );
+    fs::write(&second, "changed on disk\n").unwrap();
+    // 等待超过检查间隔
+    thread::sleep(Duration::from_millis(2100));
+    assert!(editor.run().is_err());
+    assert!(screen.text().contains("Reloaded"));
+    // 回到原来的缓冲区
+    assert_eq!(screen.row_text(0), "first");
+}
+
+#[test]
+fn saving_mixed_line_endings_asks_first() {
+    let dir = temp_dir("mixed-endings");
+    let file = create_file(&dir, "a.txt", "a\r\nb\r\nc\n");
+    let (result, _) = run(
+        &[&file],
+        vec![key(Key::End), text("!"), key(Key::Ctrl('s')), text("n\n")],
+    );
+    assert!(result.is_err());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "a\r\nb\r\nc\n");
+
+    let (result, screen) = run(
+        &[&file],
+        vec![key(Key::End), text("!"), key(Key::Ctrl('s')), text("y\n")],
+    );
+    assert!(result.is_err());
+    assert!(screen.text().contains("File saved successfully."));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "a!\r\nb\r\nc\r\n");
+}
+
+#[test]
+fn toggling_line_ending_back_is_clean() {
+    let dir = temp_dir("toggle-ending");
+    let file = create_file(&dir, "a.txt", "one\n");
+    let (result, _) = run(
+        &[&file],
+        vec![
+            key(Key::Ctrl('e')),
+            key(Key::Ctrl('e')),
+            key(Key::Ctrl('q')),
+        ],
+    );
+    assert!(result.is_ok());
+
+    let (result, screen) = run(&[&file], vec![key(Key::Ctrl('e')), key(Key::Ctrl('q'))]);
+    assert!(result.is_err());
+    assert!(screen.text().contains("Unsaved changes in"));
+}
+
+#[test]
+fn vim_undo_in_insert_mode_closes_the_insert_step() {
+    let dir = temp_dir("vim-insert-undo");
+    let file = create_file(&dir, "a.txt", "abc\n");
+    let (result, _) = run_vim(
+        &[&file],
+        vec![
+            text("xihello"),
+            key(Key::Ctrl('z')),
+            text("x"),
+            key(Key::Esc),
+            text("u:wq\n"),
+        ],
+    );
+    assert!(result.is_ok());
+    // 撤销只撤销插入模式中输入的x, 不影响之前删除a的步骤
+    assert_eq!(fs::read_to_string(&file).unwrap(), "bc\n");
+}
+
+#[test]
+fn very_wide_graphemes_line_up_with_the_cursor() {
+    let dir = temp_dir("wide-grapheme");
+    // 三个韩文初声字母组成一个字位, unicode-width计算的宽度为6, 显示时按两列处理
+    let file = create_file(&dir, "a.txt", "\u{1100}\u{1100}\u{1100}x\n");
+    let (result, screen) = run(&[&file], vec![key(Key::End)]);
+    assert!(result.is_err());
+    assert_eq!(screen.cell(2, 0).unwrap().grapheme, "x");
+    assert_eq!(screen.cursor(), (3, 0));
+}